@@ -1,157 +1,1099 @@
-use regex::Regex;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
 
-enum Operation {
-    Noop,
-    Addx(i32),
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Value(i32),
+    Register(String),
 }
 
-impl Operation {
-    fn cost(&self) -> i32 {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub name: String,
+    pub operands: Vec<Operand>,
+}
+
+// State of the processor, `x` starts at 1 and every other register at 0.
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    registers: HashMap<String, i32>,
+    // index of the next instruction to run
+    pub pc: usize,
+    // number of cycles which have completed
+    pub cycle: usize,
+    // where the instruction being executed wants to jump to, checked
+    // against the program once it's done
+    jump_to: Option<i64>,
+}
+
+impl Cpu {
+    fn new() -> Cpu {
+        Cpu {
+            registers: HashMap::from([("x".to_string(), 1)]),
+            pc: 0,
+            cycle: 0,
+            jump_to: None,
+        }
+    }
+
+    pub fn register(&self, name: &str) -> i32 {
+        *self.registers.get(name).unwrap_or(&0)
+    }
+
+    pub fn set_register(&mut self, name: &str, value: i32) {
+        self.registers.insert(name.to_string(), value);
+    }
+
+    pub fn x(&self) -> i32 {
+        self.register("x")
+    }
+
+    // Every register which has been set, sorted by name.
+    pub fn registers(&self) -> Vec<(&str, i32)> {
+        let mut registers: Vec<(&str, i32)> = self
+            .registers
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        registers.sort();
+        registers
+    }
+
+    pub fn value(&self, operand: &Operand) -> i32 {
+        match operand {
+            Operand::Value(value) => *value,
+            Operand::Register(name) => self.register(name),
+        }
+    }
+
+    // Relative to the instruction which is being executed.
+    #[cfg(test)]
+    pub fn jump(&mut self, offset: i32) {
+        self.jump_to = Some(self.pc as i64 - 1 + offset as i64);
+    }
+}
+
+// Runs once the instruction has used up all of its cycles. `pc` already
+// points at the next instruction by then.
+pub type Execute = fn(&mut Cpu, &[Operand]);
+
+#[derive(Clone, Copy)]
+pub struct InstructionDef {
+    pub cycles: usize,
+    pub arity: usize,
+    // the operand holding a relative jump offset, the only one which can
+    // name a label
+    pub target: Option<usize>,
+    pub execute: Execute,
+}
+
+#[derive(Clone)]
+pub struct InstructionSet {
+    defs: HashMap<String, InstructionDef>,
+}
+
+impl InstructionSet {
+    pub fn empty() -> InstructionSet {
+        InstructionSet {
+            defs: HashMap::new(),
+        }
+    }
+
+    // The two instructions of the handheld device.
+    pub fn standard() -> InstructionSet {
+        let mut set = InstructionSet::empty();
+        set.define("noop", 1, 0, |_, _| {});
+        set.define("addx", 2, 1, |cpu, operands| {
+            let x = cpu.x() + cpu.value(&operands[0]);
+            cpu.set_register("x", x);
+        });
+
+        set
+    }
+
+    pub fn define(&mut self, name: &str, cycles: usize, arity: usize, execute: Execute) {
+        self.defs.insert(
+            name.to_string(),
+            InstructionDef {
+                cycles,
+                arity,
+                target: None,
+                execute,
+            },
+        );
+    }
+
+    // Same as `define`, with the operand at `target` being where to jump to.
+    #[cfg(test)]
+    pub fn define_jump(&mut self, name: &str, cycles: usize, arity: usize, target: usize, execute: Execute) {
+        assert!(target < arity, "The jump target has to be one of the operands");
+        self.defs.insert(
+            name.to_string(),
+            InstructionDef {
+                cycles,
+                arity,
+                target: Some(target),
+                execute,
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InstructionDef> {
+        self.defs.get(name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VmError {
+    // the instruction at `pc` jumped outside of the program, jumping right
+    // past the last instruction is fine and ends it
+    JumpOutOfProgram { pc: usize, target: i64 },
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Operation::Noop => 1,
-            Operation::Addx(_) => 2,
+            VmError::JumpOutOfProgram { pc, target } => {
+                write!(f, "instruction {} jumps to {}, outside of the program", pc, target)
+            }
         }
     }
 }
 
-struct Device {
-    operations: Vec<Operation>,
-    current_cycle: i32,
+// Called during every cycle, before the instruction in flight finishes.
+pub trait CycleHook {
+    fn on_cycle(&mut self, cycle: usize, cpu: &Cpu);
+}
 
-    x: i32,
-    
-    display: HashMap<i32, String>,
+pub struct Vm<'a> {
+    instructions: InstructionSet,
+    hooks: Vec<&'a mut dyn CycleHook>,
+    pub cpu: Cpu,
 }
 
-impl Device {
-    fn draw(&self) {
-        println!("99999999999999999999999999999999999999999999999999999999999999999999999999999999");
+impl<'a> Vm<'a> {
+    pub fn new(instructions: InstructionSet) -> Vm<'a> {
+        Vm {
+            instructions,
+            hooks: Vec::new(),
+            cpu: Cpu::new(),
+        }
+    }
+
+    pub fn add_hook(&mut self, hook: &'a mut dyn CycleHook) {
+        self.hooks.push(hook);
+    }
+
+    // Executes a single instruction, false once the program has finished.
+    pub fn step(&mut self, program: &[Instruction]) -> Result<bool, VmError> {
+        let instruction = match program.get(self.cpu.pc) {
+            Some(instruction) => instruction,
+            None => return Ok(false),
+        };
+        let def = match self.instructions.get(&instruction.name) {
+            Some(def) => *def,
+            None => panic!("Unknown instruction {}", instruction.name),
+        };
 
-        for index in 0..240 {
-            if index != 0 && index % 40 == 0 {
-                println!();
+        for _ in 0..def.cycles {
+            self.cpu.cycle += 1;
+            for hook in self.hooks.iter_mut() {
+                hook.on_cycle(self.cpu.cycle, &self.cpu);
             }
+        }
 
-            print!("{}", self.display.get(&index)
-                .unwrap_or(&".".to_string())
-            );
+        self.cpu.pc += 1;
+        (def.execute)(&mut self.cpu, &instruction.operands);
+        if let Some(target) = self.cpu.jump_to.take() {
+            if target < 0 || target > program.len() as i64 {
+                return Err(VmError::JumpOutOfProgram { pc: self.cpu.pc - 1, target });
+            }
+            self.cpu.pc = target as usize;
         }
 
-        println!();
+        Ok(true)
     }
 
-    fn execute(&mut self) -> i32 {
-        let mut strength: i32 = 0;
-        for operation in &self.operations {
-            for _ in 0..operation.cost() {
-                if self.current_cycle == 20 || (self.current_cycle - 20) % 40 == 0 {
-                    // println!("Strength at cycle [{}]: {}", self.current_cycle, self.current_cycle * self.x);
-                    strength += self.current_cycle * self.x;
-                }
+    pub fn run(&mut self, program: &[Instruction]) -> Result<(), VmError> {
+        while self.step(program)? {}
 
-                let column = (self.current_cycle - 1) % 40;
-                if column == self.x 
-                    || column == self.x - 1
-                    || column == self.x + 1 {
-                        self.display.insert(self.current_cycle - 1, "#".to_string());
-                    }
+        Ok(())
+    }
+}
+
+// Sums up cycle * x during the 20th cycle and every 40 cycles after that.
+pub struct SignalStrength {
+    pub first: usize,
+    pub interval: usize,
+    pub total: i32,
+}
+
+impl Default for SignalStrength {
+    fn default() -> Self {
+        SignalStrength {
+            first: 20,
+            interval: 40,
+            total: 0,
+        }
+    }
+}
 
+impl CycleHook for SignalStrength {
+    fn on_cycle(&mut self, cycle: usize, cpu: &Cpu) {
+        if cycle >= self.first && (cycle - self.first).is_multiple_of(self.interval) {
+            self.total += cycle as i32 * cpu.x();
+        }
+    }
+}
 
+// The 4x6 letters the puzzles draw with, one column of space between letters.
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+const FONT: [(char, &str); 16] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
 
-                self.current_cycle += 1;
+#[derive(Debug, PartialEq, Eq)]
+pub enum OcrError {
+    WrongHeight(usize),
+    // the glyph's position on the screen and how it looks
+    UnknownGlyph { index: usize, bitmap: String },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrError::WrongHeight(height) => {
+                write!(f, "letters are {} pixels high, not {}", GLYPH_HEIGHT, height)
+            }
+            OcrError::UnknownGlyph { index, bitmap } => {
+                write!(f, "unknown glyph at position {}:\n{}", index, bitmap)
+            }
+        }
+    }
+}
+
+// Draws a pixel per cycle, lit when the 3 pixel wide sprite centered at x
+// covers the position being drawn.
+pub struct Crt {
+    pub width: usize,
+    pub height: usize,
+    pub display: Vec<bool>,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Crt {
+            width: 40,
+            height: 6,
+            display: vec![false; 40 * 6],
+        }
+    }
+}
+
+impl Crt {
+    pub fn render(&self) -> String {
+        self.display
+            .chunks(self.width)
+            .map(|row| row.iter().map(|lit| if *lit { '#' } else { '.' }).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Reads the letters off the screen.
+    pub fn read(&self) -> Result<String, OcrError> {
+        if self.height != GLYPH_HEIGHT {
+            return Err(OcrError::WrongHeight(self.height));
+        }
+
+        (0..(self.width + 1) / (GLYPH_WIDTH + 1))
+            .map(|index| {
+                let left = index * (GLYPH_WIDTH + 1);
+                let rows: Vec<String> = (0..self.height)
+                    .map(|y| {
+                        (left..left + GLYPH_WIDTH)
+                            .map(|x| if self.display[y * self.width + x] { '#' } else { '.' })
+                            .collect()
+                    })
+                    .collect();
+
+                let pixels = rows.concat();
+                FONT.iter()
+                    .find(|(_, glyph)| *glyph == pixels)
+                    .map(|(letter, _)| *letter)
+                    .ok_or(OcrError::UnknownGlyph {
+                        index,
+                        bitmap: rows.join("\n"),
+                    })
+            })
+            .collect()
+    }
+}
+
+impl CycleHook for Crt {
+    fn on_cycle(&mut self, cycle: usize, cpu: &Cpu) {
+        let position = cycle - 1;
+        if position >= self.width * self.height {
+            return;
+        }
+
+        let column = (position % self.width) as i32;
+        self.display[position] = (column - cpu.x()).abs() <= 1;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Identifier(String),
+    Number(i32),
+    // an identifier directly followed by `:`
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    // both start at 1
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnexpectedCharacter(char),
+    NumberOutOfRange(String),
+    ExpectedInstruction,
+    UnknownInstruction(String),
+    WrongArity {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    DuplicateLabel(String),
+    UnknownLabel(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match &self.kind {
+            AsmErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            AsmErrorKind::NumberOutOfRange(number) => write!(f, "{} doesn't fit in a register", number),
+            AsmErrorKind::ExpectedInstruction => write!(f, "expected an instruction"),
+            AsmErrorKind::UnknownInstruction(name) => write!(f, "unknown instruction {}", name),
+            AsmErrorKind::WrongArity {
+                name,
+                expected,
+                found,
+            } => write!(f, "{} takes {} operands, found {}", name, expected, found),
+            AsmErrorKind::DuplicateLabel(label) => write!(f, "label {} is defined twice", label),
+            AsmErrorKind::UnknownLabel(label) => write!(f, "there's no label {}", label),
+        }
+    }
+}
+
+// Splits a single line into tokens, `;` and `#` start a comment.
+fn tokenize(source: &str, line: usize) -> Result<Vec<Token>, AsmError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let error = |kind| AsmError {
+            line,
+            column: start + 1,
+            kind,
+        };
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == ';' || c == '#' {
+            break;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            let kind = if chars.get(i) == Some(&':') {
+                i += 1;
+                TokenKind::Label(name)
+            } else {
+                TokenKind::Identifier(name)
+            };
+            tokens.push(Token {
+                kind,
+                line,
+                column: start + 1,
+            });
+        } else if c.is_ascii_digit() || ((c == '-' || c == '+') && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let value = number
+                .parse()
+                .map_err(|_| error(AsmErrorKind::NumberOutOfRange(number.clone())))?;
+            tokens.push(Token {
+                kind: TokenKind::Number(value),
+                line,
+                column: start + 1,
+            });
+        } else {
+            return Err(error(AsmErrorKind::UnexpectedCharacter(c)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Turns source code into a program. Every line can start with any number of
+// `label:`s, jump targets can name a label instead of giving an offset. Any
+// other identifier is a register, even if there's a label called the same.
+pub fn assemble(source: &str, instructions: &InstructionSet) -> Result<Vec<Instruction>, AsmError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    // the instruction's index, its mnemonic and the operand tokens
+    let mut statements: Vec<(usize, Token, Vec<Token>)> = Vec::new();
+
+    for (idx, text) in source.lines().enumerate() {
+        let mut tokens = tokenize(text, idx + 1)?.into_iter().peekable();
+
+        while let Some(Token {
+            kind: TokenKind::Label(label),
+            ..
+        }) = tokens.peek().cloned()
+        {
+            let token = tokens.next().unwrap();
+            if labels.insert(label.clone(), statements.len()).is_some() {
+                return Err(AsmError {
+                    line: token.line,
+                    column: token.column,
+                    kind: AsmErrorKind::DuplicateLabel(label),
+                });
             }
+        }
+
+        if let Some(mnemonic) = tokens.next() {
+            statements.push((statements.len(), mnemonic, tokens.collect()));
+        }
+    }
 
-            match operation {
-                Operation::Noop => {}
-                Operation::Addx(value) => {
-                    self.x += *value;
+    statements
+        .into_iter()
+        .map(|(index, mnemonic, operands)| {
+            let name = match &mnemonic.kind {
+                TokenKind::Identifier(name) => name.clone(),
+                _ => {
+                    return Err(AsmError {
+                        line: mnemonic.line,
+                        column: mnemonic.column,
+                        kind: AsmErrorKind::ExpectedInstruction,
+                    })
                 }
+            };
+            let error = |kind| AsmError {
+                line: mnemonic.line,
+                column: mnemonic.column,
+                kind,
+            };
+
+            let def = instructions
+                .get(&name)
+                .ok_or(error(AsmErrorKind::UnknownInstruction(name.clone())))?;
+            if def.arity != operands.len() {
+                return Err(error(AsmErrorKind::WrongArity {
+                    name,
+                    expected: def.arity,
+                    found: operands.len(),
+                }));
+            }
+
+            let operands = operands
+                .into_iter()
+                .enumerate()
+                .map(|(position, token)| match token.kind {
+                    TokenKind::Number(value) => Ok(Operand::Value(value)),
+                    TokenKind::Identifier(name) if def.target == Some(position) => match labels.get(&name) {
+                        Some(target) => Ok(Operand::Value(*target as i32 - index as i32)),
+                        None => Err(AsmError {
+                            line: token.line,
+                            column: token.column,
+                            kind: AsmErrorKind::UnknownLabel(name),
+                        }),
+                    },
+                    TokenKind::Identifier(name) => Ok(Operand::Register(name)),
+                    TokenKind::Label(_) => Err(AsmError {
+                        line: token.line,
+                        column: token.column,
+                        kind: AsmErrorKind::UnexpectedCharacter(':'),
+                    }),
+                })
+                .collect::<Result<Vec<Operand>, AsmError>>()?;
+
+            Ok(Instruction { name, operands })
+        })
+        .collect()
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for operand in &self.operands {
+            match operand {
+                Operand::Value(value) => write!(f, " {}", value)?,
+                Operand::Register(name) => write!(f, " {}", name)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// How many instructions `disassemble` runs at most, programs can loop forever.
+const TRACE_STEPS: usize = 10_000;
+
+// Lists every instruction once, in program order. The ones which ran within
+// the first `TRACE_STEPS` get the cycle they first started in and the value
+// of x at that point.
+pub fn disassemble(program: &[Instruction], instructions: &InstructionSet) -> String {
+    let mut vm = Vm::new(instructions.clone());
+    let mut first_run: Vec<Option<(usize, i32)>> = vec![None; program.len()];
+
+    for _ in 0..TRACE_STEPS {
+        if let Some(run @ None) = first_run.get_mut(vm.cpu.pc) {
+            *run = Some((vm.cpu.cycle + 1, vm.cpu.x()));
+        }
+        // a faulty jump ends the trace just like the end of the program
+        if !matches!(vm.step(program), Ok(true)) {
+            break;
+        }
+    }
+
+    program
+        .iter()
+        .zip(first_run)
+        .enumerate()
+        .map(|(pc, (instruction, run))| match run {
+            Some((cycle, x)) => format!("{:>4}  cycle {:>4}  x={:<4} {}", pc, cycle, x, instruction),
+            None => format!("{:>4}  {:19}{}", pc, "", instruction),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    // stops after the instruction running during this cycle
+    Cycle(usize),
+    // stops once x has been set to this value
+    X(i32),
+}
+
+pub struct Debugger<'a> {
+    program: &'a [Instruction],
+    vm: Vm<'static>,
+    breakpoints: Vec<Breakpoint>,
+    // the cpu before every step, for rewinding
+    history: Vec<Cpu>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a [Instruction], instructions: InstructionSet) -> Debugger<'a> {
+        Debugger {
+            program,
+            vm: Vm::new(instructions),
+            breakpoints: Vec::new(),
+            history: Vec::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn cpu(&self) -> &Cpu {
+        &self.vm.cpu
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    // Leaves the cpu as it was when the instruction fails.
+    pub fn step(&mut self) -> Result<bool, VmError> {
+        let before = self.vm.cpu.clone();
+        match self.vm.step(self.program) {
+            Ok(false) => return Ok(false),
+            Ok(true) => {}
+            Err(why) => {
+                self.vm.cpu = before;
+                return Err(why);
             }
+        }
+        self.history.push(before);
 
+        Ok(true)
+    }
+
+    fn hit(&self, before: &Cpu) -> Option<Breakpoint> {
+        let cpu = &self.vm.cpu;
+        self.breakpoints.iter().copied().find(|breakpoint| match breakpoint {
+            Breakpoint::Cycle(cycle) => before.cycle < *cycle && *cycle <= cpu.cycle,
+            Breakpoint::X(x) => before.x() != *x && cpu.x() == *x,
+        })
+    }
+
+    // Runs until a breakpoint is hit or the program ends.
+    pub fn resume(&mut self) -> Result<Option<Breakpoint>, VmError> {
+        while self.step()? {
+            if let Some(breakpoint) = self.hit(self.history.last().unwrap()) {
+                return Ok(Some(breakpoint));
+            }
         }
 
-        self.draw();
+        Ok(None)
+    }
 
-        // println!("Total strength: {}, last_cycle: {}", strength, self.current_cycle);
-        return strength;
+    // Undoes up to `steps` instructions, returns how many were undone.
+    pub fn rewind(&mut self, steps: usize) -> usize {
+        let mut undone = 0;
+        while undone < steps {
+            match self.history.pop() {
+                Some(cpu) => self.vm.cpu = cpu,
+                None => break,
+            }
+            undone += 1;
+        }
+
+        undone
+    }
+
+    fn location(&self) -> String {
+        match self.program.get(self.vm.cpu.pc) {
+            Some(instruction) => format!(
+                "cycle {}, pc {}: {}",
+                self.vm.cpu.cycle, self.vm.cpu.pc, instruction
+            ),
+            None => format!("cycle {}, program finished", self.vm.cpu.cycle),
+        }
+    }
+
+    fn command(&mut self, line: &str) -> Option<String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let count = |idx: usize| words.get(idx).and_then(|w| w.parse().ok()).unwrap_or(1);
+
+        let output = match words.as_slice() {
+            [] => return Some(String::new()),
+            ["q" | "quit"] => return None,
+            ["s" | "step", ..] => {
+                for _ in 0..count(1) {
+                    match self.step() {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(why) => return Some(format!("{} at {}", why, self.location())),
+                    }
+                }
+                self.location()
+            }
+            ["c" | "continue"] => match self.resume() {
+                Ok(Some(breakpoint)) => format!("hit {:?} at {}", breakpoint, self.location()),
+                Ok(None) => self.location(),
+                Err(why) => format!("{} at {}", why, self.location()),
+            },
+            ["b" | "break", "cycle", value] | ["b" | "break", "x", value] => {
+                let breakpoint = match (words[1], value.parse::<i32>()) {
+                    ("cycle", Ok(cycle)) if cycle > 0 => Breakpoint::Cycle(cycle as usize),
+                    ("x", Ok(x)) => Breakpoint::X(x),
+                    _ => return Some(format!("invalid breakpoint value {}", value)),
+                };
+                self.add_breakpoint(breakpoint);
+                format!("breakpoint {}: {:?}", self.breakpoints.len(), breakpoint)
+            }
+            ["r" | "regs"] => self
+                .vm
+                .cpu
+                .registers()
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value))
+                .chain([format!("pc = {}", self.vm.cpu.pc), format!("cycle = {}", self.vm.cpu.cycle)])
+                .collect::<Vec<String>>()
+                .join("\n"),
+            ["rewind", ..] => {
+                let undone = self.rewind(count(1));
+                format!("rewound {} steps to {}", undone, self.location())
+            }
+            ["l" | "list"] => self.location(),
+            _ => format!("unknown command: {}", line.trim()),
+        };
+
+        Some(output)
+    }
+
+    // Reads commands from `input` until it runs out or gets `quit`.
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.location())?;
+        for line in input.lines() {
+            match self.command(&line?) {
+                Some(text) => writeln!(output, "{}", text)?,
+                None => break,
+            }
+        }
+
+        Ok(())
     }
 }
 
-fn parse(filename: &str) -> Vec<Operation> {
-    let file = match File::open(filename) {
+fn parse(filename: &str, instructions: &InstructionSet) -> Vec<Instruction> {
+    let source = match fs::read_to_string(filename) {
         Err(why) => panic!("Couldn't open file {}", why),
-        Ok(file) => file,
+        Ok(source) => source,
     };
 
-    let reader = io::BufReader::new(file).lines();
-
-    let op_regex = Regex::new(r"^(?P<op_name>\w+)\s?(?P<first_arg>-?\d+)?.*").unwrap();
+    match assemble(&source, instructions) {
+        Err(why) => panic!("Couldn't assemble {}: {}", filename, why),
+        Ok(program) => program,
+    }
+}
 
-    let mut ops = Vec::new();
-    for line in reader {
-        if let Ok(l) = line {
-            let captures = op_regex.captures(&l).unwrap();
-            let op_name = captures.name("op_name").unwrap().as_str();
-            let first_arg = captures.name("first_arg");
+fn execute(program: &[Instruction]) -> (SignalStrength, Crt) {
+    let mut strength = SignalStrength::default();
+    let mut crt = Crt::default();
 
-            ops.push(match op_name {
-                "noop" => Operation::Noop,
-                "addx" => Operation::Addx(first_arg.unwrap().as_str().parse().unwrap()),
-                _ => panic!("Unknown op."),
-            })
-        }
+    let mut vm = Vm::new(InstructionSet::standard());
+    vm.add_hook(&mut strength);
+    vm.add_hook(&mut crt);
+    if let Err(why) = vm.run(program) {
+        panic!("Program failed {}", why);
     }
 
-    ops
+    (strength, crt)
+}
+
+fn part1(program: &[Instruction]) -> i32 {
+    execute(program).0.total
+}
+
+fn part2(program: &[Instruction]) -> Result<String, OcrError> {
+    execute(program).1.read()
 }
 
 pub fn solve() {
-    let operations = parse("inputs/10_input");
+    let program = parse("inputs/10_input", &InstructionSet::standard());
 
-    let mut device = Device {
-        operations: operations,
-        current_cycle: 1,
-        x: 1,
+    println!("{}", part1(&program));
+    match part2(&program) {
+        Ok(letters) => println!("{}", letters),
+        Err(why) => println!("Couldn't read the screen: {}\n{}", why, execute(&program).1.render()),
+    }
+}
 
-        display: HashMap::new()
-    };
+// Steps through the puzzle input, reading commands from stdin.
+pub fn debug() {
+    let program = parse("inputs/10_input", &InstructionSet::standard());
+    println!("{}", disassemble(&program, &InstructionSet::standard()));
 
-    device.execute();
+    let mut debugger = Debugger::new(&program, InstructionSet::standard());
+    if let Err(why) = debugger.repl(io::stdin().lock(), io::stdout()) {
+        panic!("Debugger failed {}", why);
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn program(source: &str, instructions: &InstructionSet) -> Vec<Instruction> {
+        assemble(source, instructions).unwrap()
+    }
+
     #[test]
     fn sanity() {
-        let operations = vec![Operation::Noop, Operation::Addx(3), Operation::Addx(-5)];
-
-        let mut device = Device {
-            operations: operations,
-            current_cycle: 1,
-            x: 1,
-            display: HashMap::new()
-        };
+        let program = program("noop\naddx 3\naddx -5", &InstructionSet::standard());
 
-        device.execute();
+        let mut vm = Vm::new(InstructionSet::standard());
+        vm.run(&program).unwrap();
 
-        assert_eq!(-1, device.x);
+        assert_eq!(-1, vm.cpu.x());
+        assert_eq!(5, vm.cpu.cycle);
     }
-    
+
     #[test]
     fn base_case() {
-        let operations = parse("inputs/10_base");
+        let program = parse("inputs/10_base", &InstructionSet::standard());
+        let (strength, crt) = execute(&program);
 
-        let mut device = Device {
-            operations: operations,
-            current_cycle: 1,
-            x: 1,
-            display: HashMap::new()
-        };
+        assert_eq!(13140, strength.total);
+        assert_eq!(
+            "##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....",
+            crt.render()
+        );
+    }
+
+    struct Trace(Vec<(usize, i32, i32)>);
+
+    impl CycleHook for Trace {
+        fn on_cycle(&mut self, cycle: usize, cpu: &Cpu) {
+            self.0.push((cycle, cpu.x(), cpu.register("y")));
+        }
+    }
+
+    #[test]
+    fn extended_instruction_set() {
+        let mut instructions = InstructionSet::standard();
+        instructions.define("addy", 3, 1, |cpu, operands| {
+            let y = cpu.register("y") + cpu.value(&operands[0]);
+            cpu.set_register("y", y);
+        });
+        instructions.define("mul", 4, 2, |cpu, operands| {
+            if let Operand::Register(name) = &operands[0] {
+                let value = cpu.register(name) * cpu.value(&operands[1]);
+                cpu.set_register(name, value);
+            }
+        });
+        // jumps back while x is below 10
+        instructions.define_jump("jlt", 1, 2, 1, |cpu, operands| {
+            if cpu.x() < cpu.value(&operands[0]) {
+                cpu.jump(cpu.value(&operands[1]));
+            }
+        });
+
+        let program = program("addy 2\nmul x y\njlt 10 -1\naddx 1", &instructions);
+        let mut trace = Trace(Vec::new());
+        let mut vm = Vm::new(instructions);
+        vm.add_hook(&mut trace);
+        vm.run(&program).unwrap();
+
+        // x: 1 -> 2 -> 4 -> 8 -> 16, then one more for addx
+        assert_eq!(17, vm.cpu.x());
+        assert_eq!(2, vm.cpu.register("y"));
+        assert_eq!(3 + 4 * (4 + 1) + 2, vm.cpu.cycle);
+        assert_eq!((3, 1, 0), trace.0[2]);
+        assert_eq!((4, 1, 2), trace.0[3]);
+        assert_eq!((8, 2, 2), trace.0[7]);
+    }
+
+    #[test]
+    fn answers() {
+        let program = parse("inputs/10_input", &InstructionSet::standard());
+
+        assert_eq!(15680, part1(&program));
+        assert_eq!(Ok("ZFBFHGUP".to_string()), part2(&program));
+    }
+
+    #[test]
+    fn unknown_glyph() {
+        let program = parse("inputs/10_base", &InstructionSet::standard());
+
+        assert_eq!(
+            Err(OcrError::UnknownGlyph {
+                index: 0,
+                bitmap: "##..\n###.\n####\n####\n####\n####".to_string()
+            }),
+            part2(&program)
+        );
+    }
+
+    fn with_jlt() -> InstructionSet {
+        let mut instructions = InstructionSet::standard();
+        instructions.define_jump("jlt", 1, 2, 1, |cpu, operands| {
+            if cpu.x() < cpu.value(&operands[0]) {
+                cpu.jump(cpu.value(&operands[1]));
+            }
+        });
+        instructions
+    }
+
+    #[test]
+    fn labels_and_comments() {
+        let source = "# doubles x until it reaches 10
+start:
+loop:   addx x ; x += x
+        jlt 10 loop
+        jlt 20 end
+        noop
+end:";
+        let program = program(source, &with_jlt());
+
+        assert_eq!(4, program.len());
+        assert_eq!(vec![Operand::Value(10), Operand::Value(-1)], program[1].operands);
+        assert_eq!(vec![Operand::Value(20), Operand::Value(2)], program[2].operands);
+
+        let mut vm = Vm::new(with_jlt());
+        vm.run(&program).unwrap();
+        assert_eq!(16, vm.cpu.x());
+        assert_eq!(4, vm.cpu.pc);
+    }
+
+    #[test]
+    fn assembler_errors() {
+        let instructions = with_jlt();
+        let error = |source| assemble(source, &instructions).unwrap_err();
+
+        assert_eq!(
+            AsmError { line: 2, column: 6, kind: AsmErrorKind::UnexpectedCharacter('$') },
+            error("noop\naddx $3")
+        );
+        assert_eq!(
+            AsmError { line: 1, column: 1, kind: AsmErrorKind::UnknownInstruction("addz".to_string()) },
+            error("addz 1")
+        );
+        assert_eq!(
+            AsmError {
+                line: 3,
+                column: 3,
+                kind: AsmErrorKind::WrongArity { name: "addx".to_string(), expected: 1, found: 2 }
+            },
+            error("noop\n\n  addx 1 2")
+        );
+        assert_eq!(
+            AsmError { line: 2, column: 1, kind: AsmErrorKind::DuplicateLabel("a".to_string()) },
+            error("a: noop\na: noop")
+        );
+        assert_eq!(
+            AsmError { line: 1, column: 6, kind: AsmErrorKind::NumberOutOfRange("99999999999".to_string()) },
+            error("addx 99999999999")
+        );
+        assert_eq!(
+            AsmError { line: 1, column: 1, kind: AsmErrorKind::ExpectedInstruction },
+            error("12 addx")
+        );
+        assert_eq!(
+            AsmError { line: 2, column: 8, kind: AsmErrorKind::UnknownLabel("nowhere".to_string()) },
+            error("start: noop\njlt 10 nowhere")
+        );
+        assert_eq!("1:6: unexpected character '$'", error("addx $3").to_string());
+    }
+
+    #[test]
+    fn labels_only_for_jumps() {
+        let program = program("start: noop\naddx start\njlt start start", &with_jlt());
+
+        // a register which happens to be called the same as the label
+        assert_eq!(vec![Operand::Register("start".to_string())], program[1].operands);
+        assert_eq!(vec![Operand::Register("start".to_string()), Operand::Value(-2)], program[2].operands);
+    }
+
+    #[test]
+    fn disassembly() {
+        let program = program("noop\naddx 3\naddx -5\nnoop", &InstructionSet::standard());
+
+        assert_eq!(
+            "   0  cycle    1  x=1    noop
+   1  cycle    2  x=1    addx 3
+   2  cycle    4  x=4    addx -5
+   3  cycle    6  x=-1   noop",
+            disassemble(&program, &InstructionSet::standard())
+        );
+    }
+
+    #[test]
+    fn disassembly_of_loops() {
+        // loops forever, and never gets to the last noop
+        let program = program("noop\nloop: addx 0\njlt 10 loop\njlt 20 2\nnoop", &with_jlt());
+
+        assert_eq!(
+            "   0  cycle    1  x=1    noop
+   1  cycle    2  x=1    addx 0
+   2  cycle    4  x=1    jlt 10 -1
+   3                     jlt 20 2
+   4                     noop",
+            disassemble(&program, &with_jlt())
+        );
+    }
+
+    #[test]
+    fn jump_out_of_program() {
+        let instructions = with_jlt();
+        let run = |source| Vm::new(with_jlt()).run(&program(source, &instructions));
+
+        assert_eq!(Err(VmError::JumpOutOfProgram { pc: 1, target: -2 }), run("noop\njlt 10 -3"));
+        assert_eq!(Err(VmError::JumpOutOfProgram { pc: 0, target: 3 }), run("jlt 10 3\nnoop"));
+        // right past the end just finishes it
+        assert_eq!(Ok(()), run("jlt 10 2\nnoop"));
+
+        let program = program("noop\njlt 10 -3", &instructions);
+        let mut debugger = Debugger::new(&program, with_jlt());
+        let mut output = Vec::new();
+        debugger.repl("step 5\nregs".as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            "cycle 0, pc 0: noop
+instruction 1 jumps to -2, outside of the program at cycle 1, pc 1: jlt 10 -3
+x = 1
+pc = 1
+cycle = 1
+",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn debugger_breakpoints() {
+        let program = parse("inputs/10_base", &InstructionSet::standard());
+        let mut debugger = Debugger::new(&program, InstructionSet::standard());
+
+        debugger.add_breakpoint(Breakpoint::Cycle(20));
+        assert_eq!(Some(Breakpoint::Cycle(20)), debugger.resume().unwrap());
+        // the instruction spanning cycle 20 has finished by now
+        assert!(debugger.cpu().cycle >= 20 && debugger.cpu().cycle <= 21);
+
+        debugger.add_breakpoint(Breakpoint::X(7));
+        assert_eq!(Some(Breakpoint::X(7)), debugger.resume().unwrap());
+        assert_eq!(7, debugger.cpu().x());
+
+        let pc = debugger.cpu().pc;
+        assert_eq!(2, debugger.rewind(2));
+        assert_eq!(pc - 2, debugger.cpu().pc);
+    }
+
+    #[test]
+    fn debugger_repl() {
+        let program = program("noop\naddx 3\naddx -5", &InstructionSet::standard());
+        let mut debugger = Debugger::new(&program, InstructionSet::standard());
+        let commands = "step 2\nregs\nbreak x -1\ncontinue\nrewind\nwat\nquit\nstep";
+        let mut output = Vec::new();
+
+        debugger.repl(commands.as_bytes(), &mut output).unwrap();
 
-        assert_eq!(13140, device.execute());
+        assert_eq!(
+            "cycle 0, pc 0: noop
+cycle 3, pc 2: addx -5
+x = 4
+pc = 2
+cycle = 3
+breakpoint 1: X(-1)
+hit X(-1) at cycle 5, program finished
+rewound 1 steps to cycle 3, pc 2: addx -5
+unknown command: wat
+",
+            String::from_utf8(output).unwrap()
+        );
     }
 }
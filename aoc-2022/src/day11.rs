@@ -1,87 +1,88 @@
-use std::{
-    collections::{HashMap, VecDeque}, borrow::Borrow
-};
-use num_bigint::BigUint;
-
-macro_rules! vecdeque {
-    (@single $($x:tt)*) => (());
-    (@count $($rest:expr),*) => (<[()]>::len(&[$(vecdeque!(@single $rest)),*]));
-
-    ($($value:expr,)+) => { vecdeque!($($value),+) };
-    ($($value:expr),*) => {
-        {
-            let _cap = vecdeque!(@count $($value),*);
-            let mut _map = ::std::collections::VecDeque::with_capacity(_cap);
-            $(
-                _map.push_back($value);
-            )*
-            _map
-        }
-    };
-    ($value:expr;$count:expr) => {
-        {
-            let c = $count;
-            let mut _map = ::std::collections::VecDeque::with_capacity(c);
-            for _ in 0..c {
-                _map.push_back($value);
-            }
-            _map
-        }
-    };
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
 }
 
-#[derive(Debug)]
-enum Value {
+// Right hand side of `new = ...`, e.g. `old * old + 3`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
     Old,
-    Simple(u128),
+    Constant(i128),
+    Binary(Box<Expr>, Operator, Box<Expr>),
 }
 
-impl Value {
-    fn from(v: u128) -> Value {
-        Value::Simple(v)
+impl Expr {
+    fn binary(left: Expr, operator: Operator, right: Expr) -> Expr {
+        Expr::Binary(Box::new(left), operator, Box::new(right))
     }
-}
 
-#[derive(Debug)]
-enum Operator {
-    Multiply,
-    Add,
-}
+    // Exact result, None if it doesn't fit.
+    fn eval(&self, old: i128) -> Option<i128> {
+        match self {
+            Expr::Old => Some(old),
+            Expr::Constant(value) => Some(*value),
+            Expr::Binary(left, operator, right) => {
+                let (left, right) = (left.eval(old)?, right.eval(old)?);
+                match operator {
+                    Operator::Add => left.checked_add(right),
+                    Operator::Subtract => left.checked_sub(right),
+                    Operator::Multiply => left.checked_mul(right),
+                }
+            }
+        }
+    }
 
-#[derive(Debug)]
-struct Operation {
-    operator: Operator,
-    left: Value,
-    right: Value,
+    // Result modulo `modulus`, always in 0..modulus as long as `old` is.
+    fn eval_mod(&self, old: i128, modulus: i128) -> Option<i128> {
+        match self {
+            Expr::Old => Some(old),
+            Expr::Constant(value) => Some(value.rem_euclid(modulus)),
+            Expr::Binary(left, operator, right) => {
+                let (left, right) = (left.eval_mod(old, modulus)?, right.eval_mod(old, modulus)?);
+                let value = match operator {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left.checked_mul(right)?,
+                };
+                Some(value.rem_euclid(modulus))
+            }
+        }
+    }
 }
 
-// ((79 * 19) + 3 + 6) * 19
-// ((79 * 19 * 19) + 9 * 19) 
-
-// how to check if its divisible?
-// ((80 * 19 * 19) + 171) * 1/2 
-// coefs contain this num, and sum is divisible
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Constant(value) => write!(f, "{}", value),
+            Expr::Binary(left, operator, right) => {
+                let symbol = match operator {
+                    Operator::Add => '+',
+                    Operator::Subtract => '-',
+                    Operator::Multiply => '*',
+                };
+                write!(f, "({} {} {})", left, symbol, right)
+            }
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Item {
-    worry_level: u128,
-    worries: HashMap<u128, u128>,
+    worry_level: i128,
     name: String,
 }
 
 impl Item {
-    fn new(worry_level: u128) -> Item {
+    fn new_named(worry_level: i128, name: &str) -> Item {
         Item {
             worry_level,
-            name: "".to_string(),
-            worries: HashMap::new(),
-        }
-    }
-
-    fn new_named(worry_level: u128, name: &str) -> Item {
-        Item {
-            worry_level: worry_level,
-            worries: HashMap::new(),
             name: name.to_string(),
         }
     }
@@ -89,7 +90,7 @@ impl Item {
 
 #[derive(Debug)]
 struct Test {
-    divisible_by: u128,
+    divisible_by: i128,
     target_true: usize,
     target_false: usize,
 }
@@ -97,373 +98,774 @@ struct Test {
 #[derive(Debug)]
 struct Monkey {
     items: VecDeque<Item>,
-    operation: Operation,
+    operation: Expr,
     test: Test,
     inspections: u128,
 }
 
-fn round(monkeys: &mut Vec<Monkey>, very_worried: bool) {
-    let mut receive_queue: HashMap<usize, VecDeque<Item>> = HashMap::new();
+// With relief the levels have to be kept exact, see `WorryEngine`, so long
+// games only work out if the operations don't make them grow too much. An
+// `old * old` monkey squares a level every time and dividing by 3 can't keep
+// up, the example overflows an i128 in round 116. `play` gives
+// back `WorryError::Overflow` then instead of a wrong answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Relief {
+    // part 1, worry gets divided after every inspection
+    DivideBy(i128),
+    // part 2
+    None,
+}
 
-    let divisors = monkeys.iter()
-        .map(|m| m.test.divisible_by)
-        .collect::<Vec<u128>>();
+#[derive(Debug, PartialEq, Eq)]
+enum WorryError {
+    // only happens with relief, worry levels have to be kept exact then
+    Overflow { monkey: usize, worry_level: i128 },
+    // the least common multiple of the divisors is too big to multiply
+    ModulusTooLarge(i128),
+}
 
-    // hardcoding 10, because why not
-    for idx in 0..10 {
-        receive_queue.insert(idx, VecDeque::new());
+impl fmt::Display for WorryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorryError::Overflow { monkey, worry_level } => write!(
+                f,
+                "monkey {} can't inspect an item with worry level {} without overflowing",
+                monkey, worry_level
+            ),
+            WorryError::ModulusTooLarge(modulus) => {
+                write!(f, "the divisors multiply up to {}, which is too much", modulus)
+            }
+        }
     }
+}
 
-    for (idx, mut monkey) in monkeys.iter_mut().enumerate() {
-        if let Some(queue) = receive_queue.get_mut(&idx) {
-            while let Some(item) = queue.pop_front() {
-                monkey.items.push_back(item);
-            }
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Computes new worry levels. Without relief only divisibility by the monkeys'
+// divisors matters, so the levels are kept modulo their least common multiple.
+// Relief rounds the level down which doesn't play along with any modulus, so
+// those levels are kept exact. That can't always be done, `inspect` fails
+// with `WorryError::Overflow` once a level stops fitting in an i128.
+struct WorryEngine {
+    relief: Relief,
+    modulus: i128,
+}
+
+impl WorryEngine {
+    fn new(monkeys: &[Monkey], relief: Relief) -> Result<WorryEngine, WorryError> {
+        let mut modulus: i128 = 1;
+        for monkey in monkeys {
+            let divisor = monkey.test.divisible_by;
+            modulus = (modulus / gcd(modulus, divisor))
+                .checked_mul(divisor)
+                .ok_or(WorryError::ModulusTooLarge(modulus))?;
+        }
+        // products of two levels have to fit
+        if modulus.checked_mul(modulus).is_none() {
+            return Err(WorryError::ModulusTooLarge(modulus));
         }
 
-        while let Some(mut item) = monkey.items.pop_front() {
-            monkey.inspections += 1;
+        Ok(WorryEngine { relief, modulus })
+    }
 
-            let divisible: bool;
-            if !very_worried {
-                let left_value = match &monkey.operation.left {
-                    Value::Old => &item.worry_level,
-                    Value::Simple(v) => &v,
-                };
-                let right_value = match &monkey.operation.right {
-                    Value::Old => &item.worry_level,
-                    Value::Simple(v) => &v,
-                };
-    
-                item.worry_level = match monkey.operation.operator {
-                    Operator::Multiply => left_value * right_value,
-                    Operator::Add => left_value + right_value,
-                };
-                item.worry_level /= 3u128;
+    fn inspect(&self, monkey: usize, operation: &Expr, worry_level: i128) -> Result<i128, WorryError> {
+        let overflow = WorryError::Overflow { monkey, worry_level };
+        match self.relief {
+            Relief::DivideBy(divisor) => Ok(operation.eval(worry_level).ok_or(overflow)?.div_euclid(divisor)),
+            Relief::None => operation
+                .eval_mod(worry_level.rem_euclid(self.modulus), self.modulus)
+                .ok_or(overflow),
+        }
+    }
+}
 
-                let remainder = &item.worry_level % monkey.test.divisible_by;
-                divisible = remainder == 0
+fn round(monkeys: &mut [Monkey], engine: &WorryEngine, observer: &mut dyn Observer) -> Result<(), WorryError> {
+    for idx in 0..monkeys.len() {
+        let items = std::mem::take(&mut monkeys[idx].items);
+
+        for mut item in items {
+            let monkey = &mut monkeys[idx];
+            monkey.inspections += 1;
+            observer.on_inspect(idx, &item);
+            item.worry_level = engine.inspect(idx, &monkey.operation, item.worry_level)?;
+
+            let target = if item.worry_level % monkey.test.divisible_by == 0 {
+                monkey.test.target_true
             } else {
-                match &monkey.operation.right {
-                    Value::Old => {
-                        match monkey.operation.operator {
-                            Operator::Multiply => {
-                                for divisor in &divisors {
-                                    let value = item.worries.get(divisor).unwrap_or(&item.worry_level);
-                                    item.worries.insert(*divisor, value * value % divisor);
-                                }
-                            },
-                            Operator::Add => {
-                                panic!("sholdn't happen");
-                            }
-                        };
-                    },
-                    Value::Simple(v) => {
-                        match monkey.operation.operator {
-                            Operator::Multiply => {
-                                for divisor in &divisors {
-                                    let value = item.worries.get(divisor).unwrap_or(&item.worry_level);
-                                    item.worries.insert(*divisor, value * v % divisor);
-                                }
-                            },
-                            Operator::Add => {
-                                for divisor in &divisors {
-                                    let value = item.worries.get(divisor).unwrap_or(&item.worry_level);
-                                    item.worries.insert(*divisor, (value + v) % divisor);
-                                }
-                            }
-                        };
-                    }
-                };
+                monkey.test.target_false
+            };
+            monkeys[target].items.push_back(item);
+        }
+    }
+    observer.on_round_end(monkeys);
 
-                let remainder = item.worries.get(&monkey.test.divisible_by).unwrap() % monkey.test.divisible_by;
+    Ok(())
+}
 
-        
-                divisible = remainder == 0;
+// Gets told about everything happening during the rounds.
+trait Observer {
+    fn on_inspect(&mut self, monkey: usize, item: &Item);
+    fn on_round_end(&mut self, monkeys: &[Monkey]);
+}
+
+impl Observer for () {
+    fn on_inspect(&mut self, _: usize, _: &Item) {}
+    fn on_round_end(&mut self, _: &[Monkey]) {}
+}
+
+// Routes from round `start + 1` on repeat every `length` rounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cycle {
+    start: usize,
+    length: usize,
+}
+
+// Follows every item by its name, so item names have to be unique.
+struct Trace {
+    rounds: usize,
+    // monkeys which inspected the item, one list per round
+    routes: BTreeMap<String, Vec<Vec<usize>>>,
+    // inspections of every monkey so far, after each round
+    inspections: Vec<Vec<u128>>,
+    // holder and worry level of each item at the end of a round mean the
+    // item takes the same route from then on, remembers the first round
+    // every one of them was seen at
+    seen: HashMap<String, HashMap<(usize, i128), usize>>,
+    cycles: HashMap<String, Cycle>,
+}
+
+impl Trace {
+    fn new(monkeys: &[Monkey]) -> Trace {
+        let mut trace = Trace {
+            rounds: 0,
+            routes: BTreeMap::new(),
+            inspections: Vec::new(),
+            seen: HashMap::new(),
+            cycles: HashMap::new(),
+        };
+        for (_, item) in Trace::held_items(monkeys) {
+            trace.routes.insert(item.name.clone(), Vec::new());
+        }
+        trace.remember(monkeys);
+
+        trace
+    }
+
+    fn held_items(monkeys: &[Monkey]) -> impl Iterator<Item = (usize, &Item)> {
+        monkeys
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, monkey)| monkey.items.iter().map(move |item| (idx, item)))
+    }
+
+    fn remember(&mut self, monkeys: &[Monkey]) {
+        for (holder, item) in Trace::held_items(monkeys) {
+            if self.cycles.contains_key(&item.name) {
+                continue;
             }
 
-            
-            if divisible {
-                receive_queue
-                    .get_mut(&monkey.test.target_true)
-                    .unwrap()
-                    .push_back(item);
-            } else {
-                receive_queue
-                    .get_mut(&monkey.test.target_false)
-                    .unwrap()
-                    .push_back(item);
+            let seen = self.seen.entry(item.name.clone()).or_default();
+            match seen.get(&(holder, item.worry_level)) {
+                Some(start) => {
+                    let cycle = Cycle {
+                        start: *start,
+                        length: self.rounds - start,
+                    };
+                    self.cycles.insert(item.name.clone(), cycle);
+                    // not needed anymore
+                    self.seen.remove(&item.name);
+                }
+                None => {
+                    seen.insert((holder, item.worry_level), self.rounds);
+                }
             }
         }
     }
 
-    for (idx, monkey) in monkeys.iter_mut().enumerate() {
-        if let Some(queue) = receive_queue.get_mut(&idx) {
-            while let Some(item) = queue.pop_front() {
-                monkey.items.push_back(item);
+    fn route(&self, name: &str) -> Option<&Vec<Vec<usize>>> {
+        self.routes.get(name)
+    }
+
+    fn item_cycle(&self, name: &str) -> Option<Cycle> {
+        self.cycles.get(name).copied()
+    }
+
+    // The point after which every route repeats, None until every item has
+    // been traced long enough to repeat.
+    fn cycle(&self) -> Option<Cycle> {
+        self.routes.keys().try_fold(Cycle { start: 0, length: 1 }, |cycle, name| {
+            let item = self.item_cycle(name)?;
+            Some(Cycle {
+                start: cycle.start.max(item.start),
+                length: cycle.length / gcd(cycle.length as i128, item.length as i128) as usize * item.length,
+            })
+        })
+    }
+
+    // Running totals of inspections, one row per round.
+    fn inspections_csv(&self) -> String {
+        let monkeys = self.inspections.first().map_or(0, |counts| counts.len());
+        let header: Vec<String> = (0..monkeys).map(|idx| format!("monkey {}", idx)).collect();
+        let mut lines = vec![format!("round,{}", header.join(","))];
+
+        for (round, counts) in self.inspections.iter().enumerate() {
+            let counts: Vec<String> = counts.iter().map(|count| count.to_string()).collect();
+            lines.push(format!("{},{}", round + 1, counts.join(",")));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    fn inspections_json(&self) -> String {
+        let rounds: Vec<String> = self
+            .inspections
+            .iter()
+            .enumerate()
+            .map(|(round, counts)| {
+                let counts: Vec<String> = counts.iter().map(|count| count.to_string()).collect();
+                format!("{{\"round\":{},\"inspections\":[{}]}}", round + 1, counts.join(","))
+            })
+            .collect();
+
+        format!("{{\"rounds\":[{}]}}", rounds.join(","))
+    }
+}
+
+impl Observer for Trace {
+    fn on_inspect(&mut self, monkey: usize, item: &Item) {
+        let route = self.routes.entry(item.name.clone()).or_default();
+        route.resize_with(self.rounds + 1, Vec::new);
+        route[self.rounds].push(monkey);
+    }
+
+    fn on_round_end(&mut self, monkeys: &[Monkey]) {
+        self.rounds += 1;
+        self.inspections.push(monkeys.iter().map(|monkey| monkey.inspections).collect());
+        self.remember(monkeys);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum NotesError {
+    // line numbers start at 1
+    UnexpectedLine { line: usize, expected: &'static str },
+    UnexpectedEnd { expected: &'static str },
+    InvalidNumber { line: usize, value: String },
+    UnknownOperator { line: usize, operator: String },
+    WrongMonkey { line: usize, expected: usize, found: usize },
+    TargetOutOfRange { monkey: usize, target: usize },
+}
+
+impl fmt::Display for NotesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotesError::UnexpectedLine { line, expected } => {
+                write!(f, "line {}: expected {}", line, expected)
+            }
+            NotesError::UnexpectedEnd { expected } => {
+                write!(f, "notes ended, expected {}", expected)
+            }
+            NotesError::InvalidNumber { line, value } => {
+                write!(f, "line {}: {} is not a number", line, value)
+            }
+            NotesError::UnknownOperator { line, operator } => {
+                write!(f, "line {}: unknown operator {}", line, operator)
+            }
+            NotesError::WrongMonkey { line, expected, found } => {
+                write!(f, "line {}: expected monkey {}, found {}", line, expected, found)
+            }
+            NotesError::TargetOutOfRange { monkey, target } => {
+                write!(f, "monkey {} throws to monkey {} which doesn't exist", monkey, target)
             }
         }
     }
 }
 
-fn puzzle_input() -> Vec<Monkey> {
-    vec![
-            Monkey {
-                items: vecdeque![Item::new(85), Item::new(79), Item::new(63), Item::new(72)],
-                operation: Operation {
-                    operator: Operator::Multiply,
-                    left: Value::Old,
-                    right: Value::from(17),
-                },
-                test: Test {
-                    divisible_by: 2,
-                    target_true: 2,
-                    target_false: 6,
-                },
-                inspections: 0,
-            },
-            Monkey {
-                items: vecdeque![Item::new(53), Item::new(94), Item::new(65), Item::new(81), Item::new(93), Item::new(73), Item::new(57), Item::new(92)],
-                operation: Operation {
-                    operator: Operator::Multiply,
-                    left: Value::Old,
-                    right: Value::Old,
-                },
-                test: Test {
-                    divisible_by: 7,
-                    target_true: 0,
-                    target_false: 2,
-                },
-                inspections: 0,
-                
-            },
-            Monkey {
-                items: vecdeque![Item::new(62), Item::new_named(63,"midlands")],
-                operation: Operation {
-                    operator: Operator::Add,
-                    left: Value::Old,
-                    right: Value::from(7),
-                },
-                test: Test {
-                    divisible_by: 13,
-                    target_true: 7,
-                    target_false: 6,
-                },
-                inspections: 0,
-                
-            },
-            Monkey {
-                items: vecdeque![Item::new(57), Item::new(92), Item::new(56)],
-                operation: Operation {
-                    operator: Operator::Add,
-                    left: Value::Old,
-                    right: Value::from(4),
-                },
-                test: Test {
-                    divisible_by: 5,
-                    target_true: 4,
-                    target_false: 5,
-                },
-                inspections: 0,
-                
-            },
-            Monkey {
-                items: vecdeque![Item::new(67)],
-                operation: Operation {
-                    operator: Operator::Add,
-                    left: Value::Old,
-                    right: Value::from(5),
-                },
-                test: Test {
-                    divisible_by: 3,
-                    target_true: 1,
-                    target_false: 5,
-                },
-                inspections: 0,
-                
-            },
-            Monkey {
-                items: vecdeque![Item::new(85), Item::new(56), Item::new(66), Item::new(72), Item::new(57), Item::new(99)],
-                operation: Operation {
-                    operator: Operator::Add,
-                    left: Value::Old,
-                    right: Value::from(6),
-                },
-                test: Test {
-                    divisible_by: 19,
-                    target_true: 1,
-                    target_false: 0,
-                },
-                inspections: 0,
-                
-            },
-            Monkey {
-                items: vecdeque![Item::new(86), Item::new(65), Item::new(98), Item::new(97), Item::new(69)],
-                operation: Operation {
-                    operator: Operator::Multiply,
-                    left: Value::Old,
-                    right: Value::from(13),
-                },
-                test: Test {
-                    divisible_by: 11,
-                    target_true: 3,
-                    target_false: 7,
-                },
-                inspections: 0,
-                
-            },
-            Monkey {
-                items: vecdeque![Item::new(87), Item::new(68), Item::new(92), Item::new(66), Item::new(91), Item::new(50), Item::new(68)],
-                operation: Operation {
-                    operator: Operator::Add,
-                    left: Value::Old,
-                    right: Value::from(2),
-                },
-                test: Test {
-                    divisible_by: 17,
-                    target_true: 4,
-                    target_false: 3,
-                },
-                inspections: 0,
-                
-            },
-        ]
+struct Notes<'a> {
+    lines: std::iter::Peekable<std::iter::Enumerate<std::str::Lines<'a>>>,
 }
 
-pub fn solve() {
-    let mut monkeys = puzzle_input();
-
-    let result = play(&mut monkeys, true, 125);
-    println!("Result: {}", result);
-}
-
-fn play(mut monkeys: &mut Vec<Monkey>, very_worried: bool, rounds: usize) -> u128 {
-    for i in 0..rounds {
-        println!("| Round #{:03}", i);
-        // println!("--------------------- Round: {}", i);
-        
-        // for (idx, monkey) in monkeys.iter_mut().enumerate() {
-        //     // print!("[M:{},I:{}]\t", idx, monkey.items.len());
-        //     for item in &monkey.items {
-        //         print!("{}|", item.worry_level);
-        //     }
-        // }
-        // println!();
-        // println!();
-        round(&mut monkeys, very_worried);
-    }
-
-    monkeys.sort_by(|a, b| b.inspections.cmp(&a.inspections));
-    let first = monkeys.get(0).unwrap();
-    let second = monkeys.get(1).unwrap();
-
-    first.inspections * second.inspections
-}
-
-fn test_monkeys() -> Vec<Monkey> {
-    vec![
-        Monkey {
-            items: vecdeque![Item::new_named(79, "konrado"), Item::new_named(98, "moreno")],
-            operation: Operation {
-                operator: Operator::Multiply,
-                left: Value::Old,
-                right: Value::from(19),
-            },
-            test: Test {
-                divisible_by: 23,
-                target_true: 2,
-                target_false: 3,
-            },
-            inspections: 0,
-            
-        },
-        Monkey {
-            items: vecdeque![Item::new_named(54, "felicita"), Item::new(65), Item::new(75), Item::new(74)],
-            operation: Operation {
-                operator: Operator::Add,
-                left: Value::Old,
-                right: Value::from(6),
-            },
-            test: Test {
-                divisible_by: 19,
-                target_true: 2,
-                target_false: 0,
-            },
-            inspections: 0,
-            
-        },
-        Monkey {
-            items: vecdeque![Item::new_named(79, "airport"), Item::new(60), Item::new(97)],
-            operation: Operation {
-                operator: Operator::Multiply,
-                left: Value::Old,
-                right: Value::Old,
-            },
+impl<'a> Notes<'a> {
+    fn skip_blank(&mut self) {
+        while self.lines.next_if(|(_, l)| l.trim().is_empty()).is_some() {}
+    }
+
+    fn is_empty(&mut self) -> bool {
+        self.skip_blank();
+        self.lines.peek().is_none()
+    }
+
+    // The rest of the next line after `prefix`, along with its number.
+    fn field(&mut self, prefix: &'static str) -> Result<(usize, &'a str), NotesError> {
+        self.skip_blank();
+        let (idx, line) = self
+            .lines
+            .next()
+            .ok_or(NotesError::UnexpectedEnd { expected: prefix })?;
+
+        match line.trim().strip_prefix(prefix) {
+            Some(rest) => Ok((idx + 1, rest.trim())),
+            None => Err(NotesError::UnexpectedLine {
+                line: idx + 1,
+                expected: prefix,
+            }),
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(line: usize, value: &str) -> Result<T, NotesError> {
+    value.trim().parse().map_err(|_| NotesError::InvalidNumber {
+        line,
+        value: value.trim().to_string(),
+    })
+}
+
+// Recursive descent over `+`, `-` and `*` with the usual precedence and
+// parentheses, operands are `old` or integers.
+struct ExprParser<'a> {
+    line: usize,
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn new(line: usize, text: &'a str) -> ExprParser<'a> {
+        let mut tokens = Vec::new();
+        let mut rest = text.trim_start();
+        while let Some(c) = rest.chars().next() {
+            let length = if c.is_ascii_alphanumeric() {
+                rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len())
+            } else {
+                c.len_utf8()
+            };
+            tokens.push(&rest[..length]);
+            rest = rest[length..].trim_start();
+        }
+
+        ExprParser {
+            line,
+            tokens,
+            position: 0,
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        token
+    }
+
+    fn unexpected(&self) -> NotesError {
+        NotesError::UnexpectedLine {
+            line: self.line,
+            expected: "an operation like `old * 19`",
+        }
+    }
+
+    fn operator(&self) -> Result<Option<Operator>, NotesError> {
+        match self.tokens.get(self.position) {
+            None | Some(&")") => Ok(None),
+            Some(&"+") => Ok(Some(Operator::Add)),
+            Some(&"-") => Ok(Some(Operator::Subtract)),
+            Some(&"*") => Ok(Some(Operator::Multiply)),
+            Some(token) if token.chars().all(|c| c.is_ascii_alphanumeric()) => Err(self.unexpected()),
+            Some(token) => Err(NotesError::UnknownOperator {
+                line: self.line,
+                operator: token.to_string(),
+            }),
+        }
+    }
+
+    fn parse(mut self) -> Result<Expr, NotesError> {
+        let expr = self.sum()?;
+        match self.position < self.tokens.len() {
+            true => Err(self.unexpected()),
+            false => Ok(expr),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, NotesError> {
+        let mut expr = self.product()?;
+        while let Some(operator @ (Operator::Add | Operator::Subtract)) = self.operator()? {
+            self.position += 1;
+            expr = Expr::binary(expr, operator, self.product()?);
+        }
+
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, NotesError> {
+        let mut expr = self.operand()?;
+        while let Some(Operator::Multiply) = self.operator()? {
+            self.position += 1;
+            expr = Expr::binary(expr, Operator::Multiply, self.operand()?);
+        }
+
+        Ok(expr)
+    }
+
+    fn operand(&mut self) -> Result<Expr, NotesError> {
+        match self.next() {
+            Some("old") => Ok(Expr::Old),
+            Some("(") => {
+                let expr = self.sum()?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err(self.unexpected()),
+                }
+            }
+            Some(token) if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                Ok(Expr::Constant(number(self.line, token)?))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+// Reads the notes in the same format as the puzzle input, monkeys have to
+// be listed in order starting from 0.
+fn parse(notes: &str) -> Result<Vec<Monkey>, NotesError> {
+    let mut notes = Notes {
+        lines: notes.lines().enumerate().peekable(),
+    };
+    let mut monkeys = Vec::new();
+
+    while !notes.is_empty() {
+        let (line, header) = notes.field("Monkey ")?;
+        let index: usize = number(line, header.trim_end_matches(':'))?;
+        if index != monkeys.len() {
+            return Err(NotesError::WrongMonkey {
+                line,
+                expected: monkeys.len(),
+                found: index,
+            });
+        }
+
+        let (line, items) = notes.field("Starting items:")?;
+        let items = items
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .enumerate()
+            .map(|(idx, item)| number(line, item).map(|level| Item::new_named(level, &format!("{}.{}", index, idx))))
+            .collect::<Result<VecDeque<Item>, NotesError>>()?;
+
+        let (line, operation) = notes.field("Operation: new =")?;
+        let operation = ExprParser::new(line, operation).parse()?;
+
+        let (line, divisible_by) = notes.field("Test: divisible by")?;
+        let divisible_by = number(line, divisible_by)?;
+        let (line, target_true) = notes.field("If true: throw to monkey")?;
+        let target_true = number(line, target_true)?;
+        let (line, target_false) = notes.field("If false: throw to monkey")?;
+        let target_false = number(line, target_false)?;
+
+        monkeys.push(Monkey {
+            items,
+            operation,
             test: Test {
-                divisible_by: 13,
-                target_true: 1,
-                target_false: 3,
+                divisible_by,
+                target_true,
+                target_false,
             },
             inspections: 0,
-            
+        });
+    }
+
+    for (idx, monkey) in monkeys.iter().enumerate() {
+        for target in [monkey.test.target_true, monkey.test.target_false] {
+            if target >= monkeys.len() {
+                return Err(NotesError::TargetOutOfRange { monkey: idx, target });
+            }
+        }
+    }
+
+    Ok(monkeys)
+}
+
+fn read(filename: &str) -> Vec<Monkey> {
+    let notes = match fs::read_to_string(filename) {
+        Err(why) => panic!("Couldn't open file {}", why),
+        Ok(notes) => notes,
+    };
+
+    match parse(&notes) {
+        Err(why) => panic!("Couldn't read the notes in {}: {}", filename, why),
+        Ok(monkeys) => monkeys,
+    }
+}
+
+pub fn solve() {
+    for (relief, rounds) in [(Relief::DivideBy(3), 20), (Relief::None, 10000)] {
+        match play(&mut read("inputs/11_input"), relief, rounds) {
+            Ok(business) => println!("{}", business),
+            Err(why) => println!("Monkeys went wild: {}", why),
+        }
+    }
+}
+
+// Follows the items through the second part, `csv` and `json` dump the
+// inspections after every round, an item name like `0.1` prints its route and
+// without anything it tells when the routes repeat.
+pub fn trace(format: Option<&str>) {
+    let rounds = 10000;
+    let trace = match play_traced(&mut read("inputs/11_input"), Relief::None, rounds) {
+        Ok(trace) => trace,
+        Err(why) => panic!("Monkeys went wild: {}", why),
+    };
+
+    match format {
+        Some("csv") => print!("{}", trace.inspections_csv()),
+        Some("json") => println!("{}", trace.inspections_json()),
+        Some(name) => match trace.route(name) {
+            Some(route) => {
+                for (round, monkeys) in route.iter().enumerate() {
+                    let monkeys: Vec<String> = monkeys.iter().map(|idx| idx.to_string()).collect();
+                    println!("{}: {}", round + 1, monkeys.join(" -> "));
+                }
+            }
+            None => println!("There's no item {}", name),
         },
-        Monkey {
-            items: vecdeque![Item::new_named(74, "midlands")],
-            operation: Operation {
-                operator: Operator::Add,
-                left: Value::Old,
-                right: Value::from(3),
-            },
-            test: Test {
-                divisible_by: 17,
-                target_true: 0,
-                target_false: 1,
-            },
-            inspections: 0,
-            
+        None => match trace.cycle() {
+            Some(cycle) => println!(
+                "Every item repeats its route every {} rounds after round {}",
+                cycle.length, cycle.start
+            ),
+            None => println!("Not every item repeats its route within {} rounds", rounds),
         },
-    ]
+    }
+}
+
+// Plays the rounds and keeps track of every item along the way.
+fn play_traced(monkeys: &mut [Monkey], relief: Relief, rounds: usize) -> Result<Trace, WorryError> {
+    let engine = WorryEngine::new(monkeys, relief)?;
+    let mut trace = Trace::new(monkeys);
+    for _ in 0..rounds {
+        round(monkeys, &engine, &mut trace)?;
+    }
+
+    Ok(trace)
+}
+
+// Level of monkey business, inspections of the two busiest monkeys multiplied.
+fn play(monkeys: &mut [Monkey], relief: Relief, rounds: usize) -> Result<u128, WorryError> {
+    let engine = WorryEngine::new(monkeys, relief)?;
+    for _ in 0..rounds {
+        round(monkeys, &engine, &mut ())?;
+    }
+
+    let mut inspections: Vec<u128> = monkeys.iter().map(|m| m.inspections).collect();
+    inspections.sort_by_key(|count| std::cmp::Reverse(*count));
+
+    Ok(inspections.iter().take(2).product())
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    const MONKEY: &str = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3";
+
     #[test]
     fn sanity() {
-        let mut monkeys = vec![Monkey {
-            items: vecdeque![Item::new(79), Item::new(98)],
-            operation: Operation {
-                operator: Operator::Multiply,
-                left: Value::Old,
-                right: Value::from(19),
-            },
-            test: Test {
-                divisible_by: 23,
-                target_true: 2,
-                target_false: 3,
-            },
-            inspections: 0,
-            
-        }];
+        let monkeys = parse(MONKEY.replace("monkey 2", "monkey 0").replace("monkey 3", "monkey 0").as_str()).unwrap();
 
-        round(&mut monkeys, false);
+        assert_eq!(1, monkeys.len());
+        assert_eq!(vec![79, 98], monkeys[0].items.iter().map(|i| i.worry_level).collect::<Vec<i128>>());
+        assert_eq!(Expr::binary(Expr::Old, Operator::Multiply, Expr::Constant(19)), monkeys[0].operation);
+        assert_eq!(23, monkeys[0].test.divisible_by);
+    }
+
+    #[test]
+    fn any_number_of_monkeys() {
+        let notes = fs::read_to_string("inputs/11_base").unwrap();
+
+        assert_eq!(4, parse(&notes).unwrap().len());
+        assert_eq!(8, read("inputs/11_input").len());
+        // more than the old hard-coded 10 receive queues
+        let many: Vec<String> = (0..12)
+            .map(|idx| MONKEY.replace("Monkey 0", &format!("Monkey {}", idx)).replace("monkey 2", "monkey 11"))
+            .collect();
+        let mut monkeys = parse(&many.join("\n\n")).unwrap();
+        play(&mut monkeys, Relief::DivideBy(3), 20).unwrap();
+    }
 
-        // println!("{:?}", receive_queue);
-        // assert_eq!(2, receive_queue.get(&3).unwrap().len())
+    #[test]
+    fn notes_errors() {
+        assert_eq!(
+            Err(NotesError::UnknownOperator { line: 3, operator: "/".to_string() }),
+            parse(&MONKEY.replace("* 19", "/ 19")).map(|_| ())
+        );
+        assert_eq!(
+            Err(NotesError::TargetOutOfRange { monkey: 0, target: 2 }),
+            parse(MONKEY).map(|_| ())
+        );
+        assert_eq!(
+            Err(NotesError::InvalidNumber { line: 2, value: "x".to_string() }),
+            parse(&MONKEY.replace("98", "x")).map(|_| ())
+        );
+        assert_eq!(
+            Err(NotesError::WrongMonkey { line: 1, expected: 0, found: 1 }),
+            parse(&MONKEY.replace("Monkey 0", "Monkey 1")).map(|_| ())
+        );
+        assert_eq!(
+            Err(NotesError::UnexpectedEnd { expected: "If false: throw to monkey" }),
+            parse(&MONKEY[..MONKEY.rfind("    If false").unwrap()]).map(|_| ())
+        );
+        assert_eq!(
+            Err(NotesError::UnexpectedLine { line: 4, expected: "Test: divisible by" }),
+            parse(&MONKEY.replace("Test: divisible", "Test: multiple")).map(|_| ())
+        );
     }
 
     #[test]
     fn base() {
-        assert_eq!(10605, play(&mut test_monkeys(), false, 20));
+        assert_eq!(Ok(10605), play(&mut read("inputs/11_base"), Relief::DivideBy(3), 20));
     }
 
     #[test]
     fn base_part2() {
-        assert_eq!(2713310158, play(&mut test_monkeys(), true, 10000));
+        assert_eq!(Ok(2713310158), play(&mut read("inputs/11_base"), Relief::None, 10000));
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(Ok(118674), play(&mut read("inputs/11_input"), Relief::DivideBy(3), 20));
+        assert_eq!(Ok(32333418600), play(&mut read("inputs/11_input"), Relief::None, 10000));
+    }
+
+    fn operation(text: &str) -> Result<Expr, NotesError> {
+        ExprParser::new(3, text).parse()
+    }
+
+    #[test]
+    fn expressions() {
+        let expr = operation("old * old + 3").unwrap();
+        assert_eq!("((old * old) + 3)", expr.to_string());
+        assert_eq!(Some(403), expr.eval(20));
+        assert_eq!("(5 - (old * (2 + old)))", operation("5 - old * (2 + old)").unwrap().to_string());
+        assert_eq!(Some(-30), operation("5 - old * (2 + old)").unwrap().eval(5));
+        assert_eq!(Some(4), operation("5 - old * (2 + old)").unwrap().eval_mod(5, 17));
+
+        assert_eq!(Err(NotesError::UnknownOperator { line: 3, operator: "/".to_string() }), operation("old / 2"));
+        assert_eq!(Err(NotesError::UnknownOperator { line: 3, operator: "^".to_string() }), operation("old ^ 2"));
+        for broken in ["old *", "(old + 1", "old old", "", "old + 1)", "new"] {
+            assert_eq!(
+                Err(NotesError::UnexpectedLine { line: 3, expected: "an operation like `old * 19`" }),
+                operation(broken),
+                "{}",
+                broken
+            );
+        }
+    }
+
+    #[test]
+    fn modulus_matches_exact_worry() {
+        // subtraction and constants on the left, relief by 1 keeps exact levels
+        let notes = "Monkey 0:
+  Starting items: 3, 10, 12
+  Operation: new = 2 * old * old - old + 1
+  Test: divisible by 5
+    If true: throw to monkey 1
+    If false: throw to monkey 2
+
+Monkey 1:
+  Starting items: 7
+  Operation: new = 100 - old
+  Test: divisible by 3
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 1, 2
+  Operation: new = old * 3 + old
+  Test: divisible by 2
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+        let mut exact = parse(notes).unwrap();
+        let mut reduced = parse(notes).unwrap();
+        let exact_engine = WorryEngine::new(&exact, Relief::DivideBy(1)).unwrap();
+        let reduced_engine = WorryEngine::new(&reduced, Relief::None).unwrap();
+        assert_eq!(30, reduced_engine.modulus);
+        for _ in 0..3 {
+            round(&mut exact, &exact_engine, &mut ()).unwrap();
+            round(&mut reduced, &reduced_engine, &mut ()).unwrap();
+        }
+
+        for (exact, reduced) in exact.iter().zip(&reduced) {
+            assert_eq!(exact.inspections, reduced.inspections);
+            let levels: Vec<i128> = exact.items.iter().map(|i| i.worry_level.rem_euclid(30)).collect();
+            assert_eq!(levels, reduced.items.iter().map(|i| i.worry_level).collect::<Vec<i128>>());
+        }
+    }
+
+    #[test]
+    fn relief_stays_exact_on_input() {
+        // the input's levels stay small when relieved
+        assert_eq!(Ok(31648982336), play(&mut read("inputs/11_input"), Relief::DivideBy(3), 10000));
+    }
+
+    #[test]
+    fn relief_overflows_on_squares() {
+        // squaring in the example outgrows anything quickly, there's no
+        // exact answer to give
+        assert!(play(&mut read("inputs/11_base"), Relief::DivideBy(3), 115).is_ok());
+        assert!(matches!(
+            play(&mut read("inputs/11_base"), Relief::DivideBy(3), 116),
+            Err(WorryError::Overflow { monkey: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn item_routes() {
+        let trace = play_traced(&mut read("inputs/11_base"), Relief::DivideBy(3), 20).unwrap();
+
+        // 79 goes 0 -> 3 during the first round, 3 comes after 0
+        let route = trace.route("0.0").unwrap();
+        assert_eq!(20, route.len());
+        assert_eq!(vec![0, 3], route[0]);
+        assert_eq!(vec![1, 2, 3], route[1]);
+        assert_eq!(None, trace.route("4.0"));
+        // same counts as the puzzle after rounds 1 and 20
+        assert_eq!(vec![2, 4, 3, 5], trace.inspections[0]);
+        assert_eq!(vec![101, 95, 7, 105], trace.inspections[19]);
+    }
+
+    #[test]
+    fn route_cycles() {
+        let trace = play_traced(&mut read("inputs/11_input"), Relief::None, 500).unwrap();
+
+        let item = trace.item_cycle("2.0").unwrap();
+        assert_eq!(Cycle { start: 23, length: 80 }, item);
+        let route = trace.route("2.0").unwrap();
+        for round in item.start..400 {
+            assert_eq!(route[round], route[round + item.length]);
+        }
+        assert_eq!(Some(Cycle { start: 337, length: 80 }), trace.cycle());
+
+        let trace = play_traced(&mut read("inputs/11_input"), Relief::None, 100).unwrap();
+        assert_eq!(None, trace.cycle());
     }
 
     #[test]
-    fn  test_part2() {
-        assert_eq!(2713310158, play(&mut puzzle_input(), true, 10000));
+    fn inspections_export() {
+        let trace = play_traced(&mut read("inputs/11_base"), Relief::DivideBy(3), 2).unwrap();
+
+        assert_eq!("round,monkey 0,monkey 1,monkey 2,monkey 3\n1,2,4,3,5\n2,6,10,4,10\n", trace.inspections_csv());
+        assert_eq!(
+            "{\"rounds\":[{\"round\":1,\"inspections\":[2,4,3,5]},{\"round\":2,\"inspections\":[6,10,4,10]}]}",
+            trace.inspections_json()
+        );
     }
 }
@@ -1,5 +1,5 @@
-use std::cmp::min;
-use std::collections::{HashSet, HashMap, VecDeque};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::fs::File;
 use std::io::{self, BufRead};
 
@@ -48,8 +48,9 @@ struct HeightMap {
     height: i32,
     
     nodes: Vec<Vec<Node>>,
-    start: (i32, i32),
-    end: (i32, i32),
+    // there can be any number of `S`s and `E`s
+    starts: Vec<(i32, i32)>,
+    ends: Vec<(i32, i32)>,
 
 }
 
@@ -57,151 +58,363 @@ impl HeightMap {
     fn get(&self, x: i32, y: i32) -> &Node {
         self.nodes.get(y as usize).unwrap().get(x as usize).unwrap()
     }
+
+    fn contains(&self, position: (i32, i32)) -> bool {
+        position.0 >= 0 && position.0 < self.width && position.1 >= 0 && position.1 < self.height
+    }
+
+    // Draws the path the same way as the puzzle, every step points at the
+    // next one and the end of the path is marked with `E`.
+    fn render(&self, path: &[(i32, i32)]) -> String {
+        let mut rows = vec![vec!['.'; self.width as usize]; self.height as usize];
+
+        for step in path.windows(2) {
+            let (from, to) = (step[0], step[1]);
+            rows[from.1 as usize][from.0 as usize] = match (to.0 - from.0, to.1 - from.1) {
+                (1, 0) => '>',
+                (-1, 0) => '<',
+                (0, 1) => 'v',
+                (0, _) => '^',
+                (1, 1) | (-1, -1) => '\\',
+                _ => '/',
+            };
+        }
+        if let Some(last) = path.last() {
+            rows[last.1 as usize][last.0 as usize] = 'E';
+        }
+
+        rows.into_iter()
+            .map(|row| row.into_iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
 }
 
-fn parse(reader: io::Lines<io::BufReader<File>>) -> HeightMap {
+fn parse(lines: impl Iterator<Item = String>) -> HeightMap {
     let mut nodes = Vec::new();
-    let mut x = 0;
-    let mut y = 0;
-
-    let mut end = (0, 0);
-    let mut start = (0, 0);
-    for line in reader {
-        x = 0;
-        if let Ok(l) = line {
-            let mut row = Vec::new();
-
-            for letter in l.chars() {
-                let node = Node::new(
-                    x,
-                    y,
-                    letter
-                );
-                if node.is_end() {
-                    end = (x, y);
-                } 
-                if node.is_start() {
-                    start = (x, y);
-                }
-                row.push(node);
-                x += 1;
-            }
+    let mut width = 0;
 
-            nodes.push(row);
-            y += 1;
+    let mut ends = Vec::new();
+    let mut starts = Vec::new();
+    for (y, line) in lines.enumerate() {
+        let mut row = Vec::new();
+
+        for (x, letter) in line.chars().enumerate() {
+            let node = Node::new(x as i32, y as i32, letter);
+            if node.is_end() {
+                ends.push((node.x, node.y));
+            }
+            if node.is_start() {
+                starts.push((node.x, node.y));
+            }
+            row.push(node);
         }
+
+        width = row.len() as i32;
+        nodes.push(row);
     }
 
-    HeightMap { width: x, height: y, nodes, start, end }
+    HeightMap { width, height: nodes.len() as i32, nodes, starts, ends }
 }
 
-
 fn load(path: &str) -> HeightMap {
     let file = match File::open(path) {
         Err(why) => panic!("Couldn't open file {}", why),
         Ok(file) => file,
     };
 
-    let reader = io::BufReader::new(file).lines();
+    parse(io::BufReader::new(file).lines().map_while(Result::ok))
+}
 
-    parse(reader)
+const STRAIGHT: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, -1), (0, 1)];
+const DIAGONAL: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, -1), (0, 1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+// What counts as a valid step between two neighbouring cells and how much it
+// costs, the cost gets the height difference of the step (negative going down).
+#[derive(Clone, Copy)]
+struct Rules {
+    max_ascent: Option<i32>,
+    max_descent: Option<i32>,
+    diagonal: bool,
+    cost: fn(i32) -> usize,
 }
 
+impl Rules {
+    // The puzzle's rule, at most one up, as far down as you like.
+    fn climbing() -> Rules {
+        Rules {
+            max_ascent: Some(1),
+            max_descent: None,
+            diagonal: false,
+            cost: |_| 1,
+        }
+    }
 
-fn find_shortest_path(hm: &HeightMap, start: (i32, i32)) -> i32 {
-    let mut queue = VecDeque::new();
-    queue.push_back(hm.get(start.0, start.1));
+    fn allows(&self, from: i32, to: i32) -> bool {
+        let difference = to - from;
+        self.max_ascent.is_none_or(|max| difference <= max) && self.max_descent.is_none_or(|max| -difference <= max)
+    }
+
+    fn moves(&self) -> &'static [(i32, i32)] {
+        match self.diagonal {
+            true => &DIAGONAL,
+            false => &STRAIGHT,
+        }
+    }
+}
 
-    let mut visited: HashSet<(i32, i32)> = HashSet::new();
-    let mut distances: HashMap<(i32, i32), i32> = HashMap::new();
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Route {
+    cost: usize,
+    path: Vec<(i32, i32)>,
+}
 
-    let mut level = 0;
-    while queue.len() > 0 {
-        let mut level_size = queue.len();
+// Cost of getting to the closest end from every cell, found by walking
+// backwards from all the ends at once. Dijkstra, as steps can cost different
+// amounts.
+struct Descent {
+    width: i32,
+    costs: Vec<Option<usize>>,
+    // the following cell on a cheapest path towards an end
+    next: Vec<Option<(i32, i32)>>,
+}
 
-        while level_size > 0 {
-            let node = queue.pop_front().unwrap();
-            // println!("visiting node: {:?}", node);
-            level_size -= 1;
+impl Descent {
+    fn new(hm: &HeightMap, rules: &Rules) -> Descent {
+        let size = (hm.width * hm.height) as usize;
+        let mut descent = Descent {
+            width: hm.width,
+            costs: vec![None; size],
+            next: vec![None; size],
+        };
+
+        let mut queue = BinaryHeap::new();
+        for end in &hm.ends {
+            let index = descent.index(*end);
+            descent.costs[index] = Some(0);
+            queue.push(Reverse((0, *end)));
+        }
 
-            if visited.contains(&(node.x, node.y)) {
+        while let Some(Reverse((cost, position))) = queue.pop() {
+            if descent.cost(position) != Some(cost) {
+                // already got here in a cheaper way
                 continue;
             }
+            let node = hm.get(position.0, position.1);
 
-            visited.insert((node.x, node.y));
-
-            for dir in vec![(1,0), (-1, 0), (0, -1), (0, 1)] {
-                let next_pos = (node.x + dir.0, node.y + dir.1);
-                // println!("checking posi: {:?}", next_pos);
-                if next_pos.0 >= 0 && next_pos.0 < hm.width 
-                    && next_pos.1 >= 0 && next_pos.1 < hm.height {
-                    let next_node = hm.get(next_pos.0, next_pos.1);
-                    
-                    // println!("next_node: {:?}", next_node);
-                    if next_node.height - node.height > 1 {
-                        continue;
-                    }
-
-                    queue.push_back(next_node);
-
-                    // println!("inserting: {:?}", next_pos);
-                    distances.insert(
-                        next_pos,
-                        min(level + 1, *distances.get(&next_pos).unwrap_or(&999999))
-                    );
+            for (dx, dy) in rules.moves() {
+                let previous = (node.x + dx, node.y + dy);
+                if !hm.contains(previous) {
+                    continue;
+                }
+                let height = hm.get(previous.0, previous.1).height;
+                if !rules.allows(height, node.height) {
+                    continue;
                 }
+
+                let cost = cost + (rules.cost)(node.height - height);
+                if descent.cost(previous).is_some_and(|known| known <= cost) {
+                    continue;
+                }
+
+                let index = descent.index(previous);
+                descent.costs[index] = Some(cost);
+                descent.next[index] = Some(position);
+                queue.push(Reverse((cost, previous)));
             }
         }
-        level += 1;
+
+        descent
     }
 
-    
-    return *distances.get(&(hm.end.0, hm.end.1)).unwrap_or(&999999)
+    fn index(&self, position: (i32, i32)) -> usize {
+        (position.1 * self.width + position.0) as usize
+    }
+
+    // None if no end can be reached.
+    fn cost(&self, from: (i32, i32)) -> Option<usize> {
+        self.costs[self.index(from)]
+    }
+
+    // All the cells from `from` up to and including the end.
+    fn route(&self, from: (i32, i32)) -> Option<Route> {
+        let cost = self.cost(from)?;
+
+        let mut path = vec![from];
+        while let Some(next) = self.next[self.index(*path.last().unwrap())] {
+            path.push(next);
+        }
+
+        Some(Route { cost, path })
+    }
+
+    // Route from the cheapest of the cells.
+    fn cheapest(&self, cells: impl Iterator<Item = (i32, i32)>) -> Option<Route> {
+        cells
+            .filter_map(|cell| self.cost(cell).map(|cost| (cost, cell)))
+            .min()
+            .and_then(|(_, cell)| self.route(cell))
+    }
 }
 
-pub fn solve() {
-    let hm = load("inputs/12_input");
+// The cheapest route from any of the `S`s to any of the `E`s.
+fn shortest_from_start(hm: &HeightMap, rules: &Rules) -> Option<Route> {
+    Descent::new(hm, rules).cheapest(hm.starts.iter().copied())
+}
 
-    let res = find_shortest_path(&hm, hm.start);
-    println!("res: {}", res);
+// The cheapest route starting from any of the lowest cells, `S`s included.
+fn shortest_from_lowest(hm: &HeightMap, rules: &Rules) -> Option<Route> {
+    let lowest = hm
+        .nodes
+        .iter()
+        .flatten()
+        .filter(|node| node.height == 0)
+        .map(|node| (node.x, node.y));
 
-    let mut distances = Vec::new();
-    for y in 0..hm.height {
-        for x in 0..hm.width {
-            let start = (x, y);
-            let start_node = hm.get(x, y);
-            if start_node.height > 0 {
-                continue;
-            }
+    Descent::new(hm, rules).cheapest(lowest)
+}
+
+fn part1(hm: &HeightMap) -> Option<usize> {
+    shortest_from_start(hm, &Rules::climbing()).map(|route| route.cost)
+}
+
+fn part2(hm: &HeightMap) -> Option<usize> {
+    shortest_from_lowest(hm, &Rules::climbing()).map(|route| route.cost)
+}
+
+pub fn solve() {
+    let hm = load("inputs/12_input");
 
-            distances.push(find_shortest_path(&hm, start));
+    for steps in [part1(&hm), part2(&hm)] {
+        match steps {
+            Some(steps) => println!("{}", steps),
+            None => println!("There's no way up"),
         }
     }
-
-    distances.sort();
-    println!("{:?}", distances);
+    if let Some(route) = shortest_from_lowest(&hm, &Rules::climbing()) {
+        println!("{}", hm.render(&route.path));
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn map(text: &str) -> HeightMap {
+        parse(text.lines().map(String::from))
+    }
+
+    fn anywhere() -> Rules {
+        Rules {
+            max_ascent: None,
+            ..Rules::climbing()
+        }
+    }
+
     #[test]
     fn sanity() {
         let hm = load("inputs/12_base");
 
-        assert_eq!((0, 0), hm.start);
-        assert_eq!((5, 2), hm.end);
+        assert_eq!(vec![(0, 0)], hm.starts);
+        assert_eq!(vec![(5, 2)], hm.ends);
         assert_eq!(8, hm.width);
         assert_eq!(5, hm.height);
-        assert_eq!(0, hm.get(hm.start.0, hm.start.1).height);
-        assert_eq!(25, hm.get(hm.end.0, hm.end.1).height);
+        assert_eq!(0, hm.get(0, 0).height);
+        assert_eq!(25, hm.get(5, 2).height);
     }
 
     #[test]
     fn base() {
         let hm = load("inputs/12_base");
 
-        let res = find_shortest_path(&hm, hm.start);
-        assert_eq!(31, res);
+        assert_eq!(Some(31), part1(&hm));
+        assert_eq!(Some(29), part2(&hm));
+    }
+
+    #[test]
+    fn render_path() {
+        let hm = load("inputs/12_base");
+        let route = shortest_from_start(&hm, &Rules::climbing()).unwrap();
+
+        assert_eq!(31, route.path.len() - 1);
+        assert_eq!(Some(&(0, 0)), route.path.first());
+        assert_eq!(Some(&(5, 2)), route.path.last());
+        assert_eq!(
+            "v..v<<<<
+>v.vv<<^
+.v.v>E^^
+.>v>>>^^
+..>>>>>^",
+            hm.render(&route.path)
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unreachable() {
+        // the cliff in the middle is too high
+        let hm = map("Sazb\nabzE");
+
+        assert_eq!(None, part1(&hm));
+        assert_eq!(None, shortest_from_lowest(&hm, &Rules::climbing()));
+        assert_eq!(Some(0), Descent::new(&hm, &Rules::climbing()).cost((3, 1)));
+    }
+
+    #[test]
+    fn descent_limit() {
+        let hm = map("SzaE\nazzz");
+
+        assert_eq!(Some(3), shortest_from_start(&hm, &anywhere()).map(|r| r.cost));
+        let careful = Rules {
+            max_descent: Some(1),
+            ..anywhere()
+        };
+        assert_eq!(Some(5), shortest_from_start(&hm, &careful).map(|r| r.cost));
+    }
+
+    #[test]
+    fn diagonal_moves() {
+        let hm = map("Saa\naaa\naaE");
+        let diagonal = Rules {
+            diagonal: true,
+            ..anywhere()
+        };
+
+        assert_eq!(Some(4), shortest_from_start(&hm, &anywhere()).map(|r| r.cost));
+        let route = shortest_from_start(&hm, &diagonal).unwrap();
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2)], route.path);
+        assert_eq!("\\..\n.\\.\n..E", hm.render(&route.path));
+    }
+
+    #[test]
+    fn weighted_steps() {
+        // going down hurts the knees
+        let hm = map("SzaE\nazzz");
+        let knees = Rules {
+            cost: |difference| 1 + 10 * (-difference).max(0) as usize,
+            ..anywhere()
+        };
+
+        let route = shortest_from_start(&hm, &knees).unwrap();
+        assert_eq!(5, route.cost);
+        assert_eq!(vec![(0, 0), (0, 1), (1, 1), (2, 1), (3, 1), (3, 0)], route.path);
+    }
+
+    #[test]
+    fn many_markers() {
+        let hm = map("EaaSaaaaE\nSaaaaaaaa");
+
+        assert_eq!(vec![(3, 0), (0, 1)], hm.starts);
+        assert_eq!(vec![(0, 0), (8, 0)], hm.ends);
+        let route = shortest_from_start(&hm, &anywhere()).unwrap();
+        assert_eq!(vec![(0, 1), (0, 0)], route.path);
+        assert_eq!(Some(3), Descent::new(&hm, &anywhere()).cost((3, 0)));
+    }
+
+    #[test]
+    fn answers() {
+        let hm = load("inputs/12_input");
+
+        assert_eq!(Some(330), part1(&hm));
+        assert_eq!(Some(321), part2(&hm));
+    }
+}
@@ -1,920 +1,1006 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs::File;
 use std::io::{self, BufRead};
-use std::{
-    cell::{RefCell, RefMut},
-    collections::{HashMap, HashSet, VecDeque},
-    fs::File,
-    rc::Rc,
-};
+use std::str::FromStr;
 
-#[derive(Debug)]
-enum Value {
-    List(Vec<Node>),
-    Simple(u32),
-}
+use serde_json::Value;
 
-#[derive(Debug)]
-struct Node {
-    value: Value,
-    level: u32,
+#[derive(Debug, Clone)]
+enum Packet {
+    Integer(u32),
+    List(Vec<Packet>),
 }
 
-#[derive(PartialEq, Eq, Debug)]
-enum OrderingResult {
-    Same,
-    Correct,
-    Incorrect,
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    // positions are byte offsets into the line
+    UnexpectedCharacter { position: usize, found: char },
+    UnexpectedEnd,
+    NumberTooLarge { position: usize },
+    TrailingInput { position: usize },
 }
 
-impl OrderingResult {
-    fn good(&self) -> bool {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            OrderingResult::Same => true,
-            OrderingResult::Correct => true,
-            OrderingResult::Incorrect => false,
+            ParseError::UnexpectedCharacter { position, found } => {
+                write!(f, "unexpected '{}' at {}", found, position)
+            }
+            ParseError::UnexpectedEnd => write!(f, "packet ended too early"),
+            ParseError::NumberTooLarge { position } => write!(f, "number at {} is too large", position),
+            ParseError::TrailingInput { position } => write!(f, "unexpected input after the packet at {}", position),
         }
     }
 }
 
-impl Node {
-    fn parse(line: &str) -> Node {
-        let mut stack: VecDeque<Rc<RefCell<Node>>> = VecDeque::new();
-        let mut current_node: Option<Rc<RefCell<Node>>> = None;
+// packet := integer | '[' (packet (',' packet)*)? ']'
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
 
-        let mut current_level = 0;
-        let mut current_str = "".to_string();
-        for char in line.chars() {
-            if char == '[' {
-                match current_node.clone() {
-                    Some(n) => {
-                        // remember where we were
-                        stack.push_front(n.clone());
-                    }
-                    None => {}
-                }
-                current_level += 1;
-                current_node = Some(Rc::new(RefCell::new(Node::new_list(current_level))));
-            } else if char == ']' {
-                if current_str.len() > 0 {
-                    let number = current_str.parse::<u32>().unwrap();
-                    if let Some(current_node) = current_node.clone() {
-                        match current_node.borrow_mut().value {
-                            Value::List(ref mut nodes) => {
-                                println!("Pushing: {}", number);
-                                nodes.push(Node::simple(number, current_level));
-                            }
-                            Value::Simple(_) => panic!("Shoulnt be simple"),
-                        }
-                    } else {
-                        panic!("Shouldn't happen.")
-                    }
-    
-                    current_str = "".to_string();
-                }
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
 
-                current_level -= 1;
-                println!("Popping from the stack");
-                let mut old_current_node = current_node.clone();
-                current_node = stack.pop_front();
-
-                match current_node.clone() {
-                    Some(current_node) => match old_current_node.take() {
-                        Some(old_current_node) => match Rc::try_unwrap(old_current_node) {
-                            Ok(old_current_node) => {
-                                let old_current_node = old_current_node.into_inner();
-
-                                let mut current_node: RefMut<Node> = current_node.borrow_mut();
-                                match current_node.value {
-                                    Value::List(ref mut values) => {
-                                        values.push(old_current_node);
-                                    }
-                                    Value::Simple(_) => todo!(),
-                                }
-                            }
-                            Err(_) => todo!(),
-                        },
-                        None => todo!(),
-                    },
-                    None => {
-                        // set root
-                        current_node = old_current_node;
-                        // do nothing, previous was not a list
-                    }
-                }
-            } else if char == ',' {
-                if current_str.len() > 0 {
-                    let number = current_str.parse::<u32>().unwrap();
-                    if let Some(current_node) = current_node.clone() {
-                        match current_node.borrow_mut().value {
-                            Value::List(ref mut nodes) => {
-                                println!("Pushing: {}", number);
-                                nodes.push(Node::simple(number, current_level));
-                            }
-                            Value::Simple(_) => panic!("Shoulnt be simple"),
-                        }
-                    } else {
-                        panic!("Shouldn't happen.")
-                    }
-    
-                    current_str = "".to_string();
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(found) => ParseError::UnexpectedCharacter {
+                position: self.position,
+                found: found as char,
+            },
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+
+    fn packet(&mut self) -> Result<Packet, ParseError> {
+        match self.peek() {
+            Some(b'[') => self.list(),
+            Some(b'0'..=b'9') => self.integer(),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn list(&mut self) -> Result<Packet, ParseError> {
+        // skip the `[`
+        self.position += 1;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Packet::List(items));
+        }
+
+        loop {
+            items.push(self.packet()?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Packet::List(items));
                 }
-            } else {
-                current_str.push(char);
+                _ => return Err(self.unexpected()),
             }
         }
+    }
 
-        match current_node.take() {
-            Some(x) => match Rc::try_unwrap(x) {
-                Ok(d) => {
-                    let x = d.into_inner();
-                    return x;
-                }
-                Err(_) => todo!(),
-            },
-            None => todo!(),
+    fn integer(&mut self) -> Result<Packet, ParseError> {
+        let start = self.position;
+        let mut value: u32 = 0;
+        while let Some(digit @ b'0'..=b'9') = self.peek() {
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add((digit - b'0') as u32))
+                .ok_or(ParseError::NumberTooLarge { position: start })?;
+            self.position += 1;
         }
+
+        Ok(Packet::Integer(value))
     }
+}
 
-    // need to decide when to do recursive and when to do iterative
+impl Packet {
+    fn parse(line: &str) -> Result<Packet, ParseError> {
+        let mut parser = Parser {
+            text: line.as_bytes(),
+            position: 0,
+        };
+        let packet = parser.packet()?;
+        match parser.peek() {
+            None => Ok(packet),
+            Some(_) => Err(ParseError::TrailingInput {
+                position: parser.position,
+            }),
+        }
+    }
 
-    // we need to keep track whether it was already compared?
-    fn ordered_recursive(&self, right: &Node) -> OrderingResult {
-        // [1, [1,2,3]]
-        // [1,2,]
-        println!(
-            "Ordering node at level: {} = {} vs {}",
-            self.level,
-            self.print(),
-            right.print()
-        );
-        match self.value {
-            // starting with [1,2,3,4]
-            Value::List(ref values) => {
-                // println!("Left value is list: {:?}", self.value);
-                for (left_node_idx, left_node) in values.iter().enumerate() {
-                    // println!(
-                    // "Processing node index: {}, at level: {} = {:?}",
-                    // left_node_idx, left_node.level, left_node
-                    // );
-
-                    match left_node.value {
-                        Value::List(_) => {
-                            println!("Left node is list, so going deeper.");
-                            match right.value {
-                                // [1,2,3,4] vs [1,2,3]
-                                // [1,2,3,4] vs []
-                                Value::List(ref right_deep_values) => {
-                                    if let Some(right_deep_node) =
-                                        right_deep_values.get(left_node_idx)
-                                    {
-                                        match right_deep_node.value {
-                                            // [1,2,3] vs [[1,2,3]] => [1] vs [1,2,3]
-                                            Value::List(_) => {
-                                                println!("Right deep value is a list");
-                                                match left_node.ordered_recursive(right_deep_node) {
-                                                    OrderingResult::Same => {}
-                                                    res => {
-                                                        return res;
-                                                    }
-                                                }
-                                            }
-                                            Value::Simple(right_deep_value_number) => {
-                                                println!("Right deep value is simple");
-                                                let new_node = Node::simple_list(
-                                                    vec![right_deep_value_number],
-                                                    self.level + 1,
-                                                );
-                                                match left_node.ordered_recursive(&new_node) {
-                                                    OrderingResult::Same => {}
-                                                    res => {
-                                                        return res;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    } else {
-                                        println!("There is no items");
-                                        // we ran out of the items
-                                        return OrderingResult::Incorrect;
-                                    }
-                                }
-                                Value::Simple(_) => todo!("Shouldn't happen?"),
-                            }
-
-                            // // TODO: should we pass the current path here?
-                            // match left_node.ordered_recursive(right) {
-                            //     // Same, means that what?
-                            //     // There was the same number of items on the left and on the right.
-                            //     // WE need to proceed further.
-                            //     OrderingResult::Same => {}
-                            //     res => {
-                            //         return res;
-                            //     }
-                            // }
-                        }
-                        // as well ass [1,2,3] vs []
-                        // [1,2,3,4] vs [1,2,3,4]
-                        // but also
-                        // [1,2,3,4] vs [[1],2,[3,4]]
-                        // => [1] vs [1]
-                        // => 2 vs 2
-                        // => [3] s [3,4]
-                        // => 4 vs nothing
-                        Value::Simple(left_value_number) => {
-                            println!("Left node is a number, so we want to compare.");
-
-                            match right.value {
-                                // [1,2,3,4] vs [1,2,3]
-                                // [1,2,3,4] vs []
-                                Value::List(ref right_deep_values) => {
-                                    if let Some(right_deep_node) =
-                                        right_deep_values.get(left_node_idx)
-                                    {
-                                        match right_deep_node.value {
-                                            // [1,2,3] vs [[1,2,3]] => [1] vs [1,2,3]
-                                            Value::List(_) => {
-                                                let new_node = Node::simple_list(
-                                                    vec![left_value_number],
-                                                    self.level + 1,
-                                                );
-                                                match new_node.ordered_recursive(right_deep_node) {
-                                                    OrderingResult::Same => {}
-                                                    res => {
-                                                        return res;
-                                                    }
-                                                }
-                                            }
-                                            Value::Simple(right_deep_value_number) => {
-                                                println!(
-                                                    "Comparing: {} to {}",
-                                                    left_value_number, right_deep_value_number
-                                                );
-                                                if left_value_number < right_deep_value_number {
-                                                    return OrderingResult::Correct;
-                                                } else if left_value_number
-                                                    > right_deep_value_number
-                                                {
-                                                    return OrderingResult::Incorrect;
-                                                }
-                                            }
-                                        }
-                                    } else {
-                                        println!("There is no items");
-                                        // we ran out of the items
-                                        return OrderingResult::Incorrect;
-                                    }
-                                }
-                                Value::Simple(_) => todo!("Shouldn't happen?"),
-                            }
-                        }
+    fn divider(value: u32) -> Packet {
+        Packet::List(vec![Packet::List(vec![Packet::Integer(value)])])
+    }
+}
+
+impl FromStr for Packet {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Packet, ParseError> {
+        Packet::parse(s)
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Integer(value) => write!(f, "{}", value),
+            Packet::List(items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
                     }
+                    write!(f, "{}", item)?;
                 }
-                println!("Reached end of the life.");
+                write!(f, "]")
+            }
+        }
+    }
+}
 
+// Lists compare item by item and the shorter one goes first when they run
+// out, an integer compared with a list gets wrapped in a list of its own.
+impl Ord for Packet {
+    fn cmp(&self, other: &Packet) -> Ordering {
+        match (self, other) {
+            (Packet::Integer(left), Packet::Integer(right)) => left.cmp(right),
+            (Packet::List(left), Packet::List(right)) => left.iter().cmp(right.iter()),
+            (Packet::Integer(left), Packet::List(_)) => Packet::List(vec![Packet::Integer(*left)]).cmp(other),
+            (Packet::List(_), Packet::Integer(right)) => self.cmp(&Packet::List(vec![Packet::Integer(*right)])),
+        }
+    }
+}
 
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Packet) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-                if values.len() == 0 {
-                    return match right.value {
-                        Value::List(ref right_values) => {
-                            if right_values.len() == 0 {
-                                OrderingResult::Same
-                            } else {
-                                OrderingResult::Correct
-                            }
-                        }
-                        Value::Simple(_) => OrderingResult::Correct
-                    }
-                } else {
-                    return match right.value {
-                        Value::List(ref right_values) => {
-                            if values.len() < right_values.len() {
-                                OrderingResult::Correct
-                            } else {
-                                OrderingResult::Same
-                            }
+// Has to agree with `Ord`, so `[[1]]` and `1` are equal.
+impl PartialEq for Packet {
+    fn eq(&self, other: &Packet) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Packet {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+// Which of the rules decided the order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rule {
+    IntegerCompare,
+    // one of the lists ran out of items first
+    ListLength,
+    // the integer on one side got wrapped in a list, the rest was decided
+    // by comparing that list
+    Promotion { promoted: Side, then: Box<Rule> },
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::IntegerCompare => write!(f, "integer compare"),
+            Rule::ListLength => write!(f, "list length"),
+            Rule::Promotion { promoted, then } => write!(f, "{} integer promoted to a list, then {}", promoted, then),
+        }
+    }
+}
+
+// The first elements which differ, a list which ran out has no element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Difference {
+    // indices into the lists from the top, promoted integers count as
+    // lists with a single item
+    path: Vec<usize>,
+    left: Option<Packet>,
+    right: Option<Packet>,
+    rule: Rule,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Explanation {
+    ordering: Ordering,
+    // None for equal packets
+    difference: Option<Difference>,
+}
+
+fn find_difference(left: &Packet, right: &Packet, path: &mut Vec<usize>) -> Option<(Ordering, Difference)> {
+    match (left, right) {
+        (Packet::Integer(l), Packet::Integer(r)) => match l.cmp(r) {
+            Ordering::Equal => None,
+            ordering => Some((
+                ordering,
+                Difference {
+                    path: path.clone(),
+                    left: Some(left.clone()),
+                    right: Some(right.clone()),
+                    rule: Rule::IntegerCompare,
+                },
+            )),
+        },
+        (Packet::List(l), Packet::List(r)) => {
+            for idx in 0..l.len().max(r.len()) {
+                path.push(idx);
+                let found = match (l.get(idx), r.get(idx)) {
+                    (Some(left), Some(right)) => find_difference(left, right, path),
+                    (left, right) => Some((
+                        l.len().cmp(&r.len()),
+                        Difference {
+                            path: path.clone(),
+                            left: left.cloned(),
+                            right: right.cloned(),
+                            rule: Rule::ListLength,
                         },
-                        Value::Simple(_) => todo!(),
-                    }
-                }
+                    )),
+                };
+                path.pop();
 
-                // if it reached the end
-                // we might want to return Same, because upper function call will need to continue.
-                // we might want to return Correct, because it was empty.
-            }
-            // 1, 2
-            // 1, [[2]]
-            // 1, [2]
-            Value::Simple(_) => {
-                todo!("Shouldn't happen");
+                if found.is_some() {
+                    return found;
+                }
             }
+
+            None
+        }
+        (Packet::Integer(value), Packet::List(_)) | (Packet::List(_), Packet::Integer(value)) => {
+            let promoted = match left {
+                Packet::Integer(_) => Side::Left,
+                Packet::List(_) => Side::Right,
+            };
+            let wrapped = Packet::List(vec![Packet::Integer(*value)]);
+            let (ordering, mut difference) = match promoted {
+                Side::Left => find_difference(&wrapped, right, path)?,
+                Side::Right => find_difference(left, &wrapped, path)?,
+            };
+            difference.rule = Rule::Promotion {
+                promoted,
+                then: Box::new(difference.rule),
+            };
+
+            Some((ordering, difference))
         }
     }
+}
 
-    fn ordered_recursive_old(&self, right: &Node) -> OrderingResult {
-        // [1, [1,2,3]]
-        // [1,2,]
-        match self.value {
-            // starting with [1,2,3,4]
-            Value::List(ref values) => {
-                match right.value {
-                    Value::List(ref right_values) => {
-                        println!("Comparing two lists: {:?} and {:?}", values, right_values);
-                        for (left_node_idx, left_node) in values.iter().enumerate() {
-                            println!("Processing: {:?}", left_node);
-                            if let Some(right_node) = right_values.get(left_node_idx) {
-                                match left_node.value {
-                                    // [[1,2,3]], [1,2,3]
-                                    Value::List(_) => {
-                                        println!("Doing some weird logic");
-
-                                        match right_node.value {
-                                            Value::List(_) => {
-                                                match left_node.ordered_recursive(right_node) {
-                                                    OrderingResult::Same => {}
-                                                    OrderingResult::Correct => {}
-                                                    res => {
-                                                        return res;
-                                                    }
-                                                }
-                                            }
-                                            Value::Simple(right_inner_value_number) => {
-                                                match left_node.ordered_recursive(
-                                                    &Node::simple_list(
-                                                        vec![right_inner_value_number],
-                                                        right_node.level,
-                                                    ),
-                                                ) {
-                                                    OrderingResult::Same => {}
-                                                    OrderingResult::Correct => {}
-                                                    res => {
-                                                        return res;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    // [1,2,3] vs [[1], 2, 3]
-                                    // [1,2,3] vs [[1,2],3 ]
-                                    Value::Simple(left_inner_value_number) => {
-                                        match right_node.value {
-                                            // 1 vs [1]
-                                            // 1 vs [1,2]
-                                            Value::List(_) => {
-                                                let new_node = Node::simple_list(
-                                                    vec![left_inner_value_number],
-                                                    left_node.level,
-                                                );
-                                                // 1 vs [1]
-                                                // turns into [1] vs [1]
-                                                println!(
-                                                    "Converting {:?} into {:?}",
-                                                    left_node, new_node
-                                                );
-                                                match new_node.ordered_recursive(right_node) {
-                                                    OrderingResult::Same => {}
-                                                    OrderingResult::Correct => {}
-                                                    res => {
-                                                        return res;
-                                                    }
-                                                }
-                                            }
-                                            Value::Simple(right_inner_value_number) => {
-                                                println!(
-                                                    "Comparring {} vs {}",
-                                                    left_inner_value_number,
-                                                    right_inner_value_number
-                                                );
-                                                if left_inner_value_number
-                                                    < right_inner_value_number
-                                                {
-                                                    return OrderingResult::Correct;
-                                                } else if left_inner_value_number
-                                                    > right_inner_value_number
-                                                {
-                                                    return OrderingResult::Incorrect;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            } else {
-                                return OrderingResult::Incorrect;
-                            }
-                        }
-                    }
-                    // [4,1] vs 5 -> Incorrect
-                    // [] vs 5 -> Correc
-                    // [4] vs 5 -> Correct
-                    // [[4]] vs 5 -> process next
-                    Value::Simple(right_value_number) => {
-                        if values.len() > 1 {
-                            return OrderingResult::Incorrect;
-                        } else if values.len() == 0 {
-                            return OrderingResult::Correct;
-                        }
-
-                        for (left_node_idx, left_node) in values.iter().enumerate() {
-                            match left_node.value {
-                                Value::List(_) => {
-                                    match left_node.ordered_recursive(&Node::simple_list(
-                                        vec![right_value_number],
-                                        right.level,
-                                    )) {
-                                        OrderingResult::Same => {}
-                                        OrderingResult::Correct => {}
-                                        res => {
-                                            return res;
-                                        }
-                                    }
-                                }
-                                Value::Simple(left_value_number) => {
-                                    println!(
-                                        "Comparing: {} to {}",
-                                        left_value_number, right_value_number
-                                    );
-                                    if left_value_number < right_value_number {
-                                        return OrderingResult::Correct;
-                                    } else if left_value_number > right_value_number {
-                                        return OrderingResult::Incorrect;
-                                    }
-                                }
-                            }
-                        }
-
-                        // len == 0, [] vs 5
-                        // len == 0, []
-                        // ??
-                        return OrderingResult::Correct;
-                    }
+impl Packet {
+    // Same result as `cmp`, along with the reason for it.
+    fn explain(&self, other: &Packet) -> Explanation {
+        match find_difference(self, other, &mut Vec::new()) {
+            Some((ordering, difference)) => Explanation {
+                ordering,
+                difference: Some(difference),
+            },
+            None => Explanation {
+                ordering: Ordering::Equal,
+                difference: None,
+            },
+        }
+    }
+
+    // One element per line, the lines of the element at `target` are marked.
+    // A target past the end of a list marks the closing bracket, one going
+    // into an integer marks the integer.
+    fn pretty_lines(&self, depth: usize, target: Option<&[usize]>, lines: &mut Vec<(String, bool)>) {
+        let indent = "  ".repeat(depth);
+        let here = target.is_some_and(|target| target.is_empty());
+
+        match self {
+            Packet::Integer(value) => lines.push((format!("{}{}", indent, value), target.is_some())),
+            Packet::List(items) if items.is_empty() => {
+                let ran_out = target.is_some_and(|target| !target.is_empty());
+                lines.push((format!("{}[]", indent), here || ran_out));
+            }
+            Packet::List(items) => {
+                lines.push((format!("{}[", indent), here));
+                for (idx, item) in items.iter().enumerate() {
+                    let target = target.and_then(|target| match target.split_first() {
+                        Some((first, rest)) if *first == idx => Some(rest),
+                        _ => None,
+                    });
+                    item.pretty_lines(depth + 1, target, lines);
                 }
+                let ran_out = target.is_some_and(|target| target.len() == 1 && target[0] >= items.len());
+                lines.push((format!("{}]", indent), here || ran_out));
             }
-            // 1, 2
-            // 1, [[2]]
-            // 1, [2]
-            Value::Simple(left_value_number) => todo!(),
         }
+    }
+}
+
+impl Explanation {
+    fn summary(&self) -> String {
+        let verdict = match self.ordering {
+            Ordering::Less => "right order",
+            Ordering::Equal => "equal",
+            Ordering::Greater => "wrong order",
+        };
+        let difference = match &self.difference {
+            Some(difference) => difference,
+            None => return verdict.to_string(),
+        };
+
+        let path: String = difference.path.iter().map(|idx| format!("[{}]", idx)).collect();
+        let value = |packet: &Option<Packet>| match packet {
+            Some(packet) => packet.to_string(),
+            None => "nothing".to_string(),
+        };
+        let symbol = match self.ordering {
+            Ordering::Less => "<",
+            Ordering::Equal => "=",
+            Ordering::Greater => ">",
+        };
+
+        format!(
+            "{} at {}: {} {} {} ({})",
+            verdict,
+            if path.is_empty() { "the top" } else { &path },
+            value(&difference.left),
+            symbol,
+            value(&difference.right),
+            difference.rule
+        )
+    }
+
+    // Both packets next to each other with the deciding elements marked.
+    fn render(&self, left: &Packet, right: &Packet) -> String {
+        let target = self.difference.as_ref().map(|difference| difference.path.as_slice());
+        let (mut left_lines, mut right_lines) = (Vec::new(), Vec::new());
+        left.pretty_lines(0, target, &mut left_lines);
+        right.pretty_lines(0, target, &mut right_lines);
+
+        let width = left_lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0).max(4);
+        let mut rows = vec![format!("  {:<width$} |   right", "left", width = width)];
+        for idx in 0..left_lines.len().max(right_lines.len()) {
+            let (left_line, left_marked) = left_lines.get(idx).cloned().unwrap_or_default();
+            let (right_line, right_marked) = right_lines.get(idx).cloned().unwrap_or_default();
+            let row = format!(
+                "{} {:<width$} | {} {}",
+                if left_marked { '>' } else { ' ' },
+                left_line,
+                if right_marked { '>' } else { ' ' },
+                right_line,
+                width = width
+            );
+            rows.push(row.trim_end().to_string());
+        }
+        rows.push(self.summary());
+
+        rows.join("\n")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum JsonError {
+    // not JSON at all, with serde's description of what's wrong
+    Syntax(String),
+    // only arrays and non-negative integers which fit in u32 make packets
+    Unsupported(String),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Syntax(why) => write!(f, "invalid JSON: {}", why),
+            JsonError::Unsupported(value) => write!(f, "{} can't be a part of a packet", value),
+        }
+    }
+}
 
-        return OrderingResult::Correct;
-    }
-
-    // todo, create it in the recurrent manner.
-    // fn ordered<'a>(&'a self, right: &Node) -> bool {
-    //     let mut borrowable_values: HashMap<u32, Value> = HashMap::new();
-    //     for i in 0..10 {
-    //         borrowable_values.insert(i, Value::List(vec![Node::simple(i)]));
-    //     }
-
-    //     let mut left_queue: VecDeque<&Value> = VecDeque::new();
-    //     let mut right_queue: VecDeque<&Value> = VecDeque::new();
-
-    //     left_queue.push_back(&self.value);
-    //     right_queue.push_back(&right.value);
-
-    //     while left_queue.len() > 0 {
-    //         println!("left queue: {:?}", left_queue);
-    //         println!("right queue: {:?}", right_queue);
-    //         let left_value = left_queue.pop_front().unwrap();
-    //         if right_queue.len() == 0 {
-    //             println!("returning because of the empty queue");
-    //             return false;
-    //         }
-    //         let right_value = right_queue.pop_front().unwrap();
-
-    //         // convert to a list, instead of converting to a number!
-    //         match left_value {
-    //             Value::List(left_values) => {
-    //                 println!("!!!! left is list, {:?}", left_value);
-
-    //                 match right_value {
-    //                     Value::List(right_values) => {
-    //                         let mut last_right_idx = 0;
-    //                         println!("#### right is list, {:?}", right_value);
-    //                         for (left_idx, left_node) in left_values.iter().enumerate().rev() {
-    //                             left_queue.push_front(&left_node.value);
-
-    //                             if let Some(right_node) = right_values.get(left_idx) {
-    //                                 last_right_idx = left_idx;
-    //                                 match right_node.value {
-    //                                     Value::List(_) => right_queue.push_front(&right_node.value),
-    //                                     Value::Simple(right_value) => match left_node.value {
-    //                                         Value::List(_) => {
-    //                                             left_queue.push_front(&left_node.value);
-    //                                             right_queue.push_front(
-    //                                                 &borrowable_values.get(&right_value).unwrap(),
-    //                                             )
-    //                                         }
-    //                                         Value::Simple(left_value) => {
-    //                                             if left_value < right_value {
-    //                                                 return true;
-    //                                             } else if left_value > right_value {
-    //                                                 return false;
-    //                                             }
-    //                                         }
-    //                                     },
-    //                                 }
-    //                                 right_queue.push_front(&right_node.value);
-    //                             }
-    //                         }
-
-    //                         for idx in last_right_idx..right_values.len() {
-    //                             right_queue.push_back(&right_values.get(idx).unwrap().value);
-    //                         }
-    //                     }
-    //                     Value::Simple(v) => {
-    //                         left_queue.push_front(&left_value);
-    //                         right_queue.push_front(&borrowable_values.get(v).unwrap());
-    //                     }
-    //                 }
-    //             }
-    //             Value::Simple(left_v) => {
-    //                 println!("!!! left is simple {:?}", left_value);
-    //                 match right_value {
-    //                     Value::List(_) => {
-    //                         println!("### right is list {:?}", right_value);
-    //                         left_queue.push_front(&borrowable_values.get(left_v).unwrap());
-    //                         right_queue.push_front(right_value);
-    //                     }
-    //                     Value::Simple(right_v) => {
-    //                         println!("comparing: {} vs {}", left_v, right_v);
-    //                         if left_v > right_v {
-    //                             return false;
-    //                         } else if left_v < right_v {
-    //                             return true;
-    //                         }
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     }
-
-    //     return true;
-    // }
-
-    fn new_list(level: u32) -> Node {
-        Node {
-            value: Value::List(vec![]),
-            level,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Stats {
+    // a bare integer is 0 deep, `[]` is 1
+    depth: usize,
+    integers: usize,
+    lists: usize,
+}
+
+impl Packet {
+    fn to_json(&self) -> Value {
+        match self {
+            Packet::Integer(value) => Value::from(*value),
+            Packet::List(items) => Value::Array(items.iter().map(Packet::to_json).collect()),
         }
     }
 
-    fn simple(num: u32, level: u32) -> Node {
-        Node {
-            value: Value::Simple(num),
-            level,
+    fn from_json(value: &Value) -> Result<Packet, JsonError> {
+        match value {
+            Value::Number(number) => number
+                .as_u64()
+                .and_then(|number| u32::try_from(number).ok())
+                .map(Packet::Integer)
+                .ok_or(JsonError::Unsupported(number.to_string())),
+            Value::Array(items) => Ok(Packet::List(
+                items.iter().map(Packet::from_json).collect::<Result<Vec<Packet>, JsonError>>()?,
+            )),
+            other => Err(JsonError::Unsupported(other.to_string())),
         }
     }
 
-    fn simple_list(nums: Vec<u32>, level: u32) -> Node {
-        Node {
-            value: Value::List(nums.iter().map(|v| Node::simple(*v, level + 1)).collect()),
-            level,
+    // Any JSON text made of arrays and integers, whitespace included.
+    fn from_json_str(text: &str) -> Result<Packet, JsonError> {
+        let value: Value = serde_json::from_str(text).map_err(|why| JsonError::Syntax(why.to_string()))?;
+        Packet::from_json(&value)
+    }
+
+    fn pretty(&self) -> String {
+        serde_json::to_string_pretty(&self.to_json()).unwrap()
+    }
+
+    fn stats(&self) -> Stats {
+        match self {
+            Packet::Integer(_) => Stats {
+                integers: 1,
+                ..Stats::default()
+            },
+            Packet::List(items) => items.iter().map(Packet::stats).fold(
+                Stats {
+                    depth: 1,
+                    integers: 0,
+                    lists: 1,
+                },
+                |total, item| Stats {
+                    depth: total.depth.max(item.depth + 1),
+                    integers: total.integers + item.integers,
+                    lists: total.lists + item.lists,
+                },
+            ),
         }
     }
 
-    fn print(&self) -> String {
-        match self.value {
-            Value::List(ref l) => {
-                let mut xd = "[".to_string();
-                for (idx, n) in l.iter().enumerate() {
-                    xd.push_str(&n.print());
-                    if idx != l.len() - 1 {
-                        xd.push(',');
-                    }
-                }
-                xd.push(']');
+    // All the integers, left to right.
+    // Adds up every integer, as u64 since a handful of large ones overflow u32.
+    fn total(&self) -> u64 {
+        self.flatten().iter().map(|value| *value as u64).sum()
+    }
 
-                xd
+    fn flatten(&self) -> Vec<u32> {
+        match self {
+            Packet::Integer(value) => vec![*value],
+            Packet::List(items) => items.iter().flat_map(Packet::flatten).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ReadError {
+    Io(io::Error),
+    // line numbers start at 1
+    Packet { line: usize, error: ParseError },
+    // the input ended after the first packet of a pair
+    UnpairedPacket { line: usize },
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(why) => write!(f, "{}", why),
+            ReadError::Packet { line, error } => write!(f, "line {}: {}", line, error),
+            ReadError::UnpairedPacket { line } => write!(f, "the packet on line {} has no pair", line),
+        }
+    }
+}
+
+// Reads pairs of packets one by one. Blank lines are optional, any number of
+// them can be anywhere, and so is whitespace (`\r` included) around packets.
+struct Pairs<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> Pairs<R> {
+    fn new(reader: R) -> Pairs<R> {
+        Pairs {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+
+    fn next_packet(&mut self) -> Option<Result<Packet, ReadError>> {
+        for line in self.lines.by_ref() {
+            self.line += 1;
+            let line = match line {
+                Err(why) => return Some(Err(ReadError::Io(why))),
+                Ok(line) => line,
+            };
+            if line.trim().is_empty() {
+                continue;
             }
-            Value::Simple(num) => format!("{}", num),
+
+            return Some(Packet::parse(line.trim()).map_err(|error| ReadError::Packet {
+                line: self.line,
+                error,
+            }));
         }
+
+        None
     }
 }
 
-pub fn solve() {
-    let file = match File::open("inputs/13_input") {
+impl<R: BufRead> Iterator for Pairs<R> {
+    type Item = Result<(Packet, Packet), ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let left = match self.next_packet()? {
+            Err(why) => return Some(Err(why)),
+            Ok(left) => left,
+        };
+        let line = self.line;
+
+        Some(match self.next_packet() {
+            None => Err(ReadError::UnpairedPacket { line }),
+            Some(right) => right.map(|right| (left, right)),
+        })
+    }
+}
+
+fn read(filename: &str) -> Vec<(Packet, Packet)> {
+    let file = match File::open(filename) {
         Err(why) => panic!("Couldn't open file {}", why),
         Ok(file) => file,
     };
 
-    let reader = io::BufReader::new(file).lines();
-
-    let mut ordered = vec![];
-    let mut pair_index: usize = 0;
-    let mut left: Option<Node> = None;
-    let mut right: Option<Node> = None;
-
-    let mut nodes = vec![];
-    for line in reader {
-        if let Ok(line) = line {
-            if left.is_none() {
-                pair_index += 1;
-                left = Some(Node::parse(&line));
-                nodes.push(Node::parse(&line));
-            } else if right.is_none() {
-                right = Some(Node::parse(&line));
-                nodes.push(Node::parse(&line));
-            } else {
-                if left.unwrap().ordered_recursive(&right.unwrap()).good() {
-                    ordered.push(pair_index);
-                }
-                left = None;
-                right = None;
-            }
-        }
+    match Pairs::new(io::BufReader::new(file)).collect() {
+        Err(why) => panic!("Couldn't read {}: {}", filename, why),
+        Ok(pairs) => pairs,
     }
+}
 
-    nodes.push(Node::parse("[[2]]"));
-    nodes.push(Node::parse("[[6]]"));
-    // 12 -> incorrect
-    // 25 -> correct
-    // 42 -> correct
-    // 44 > incorrect
+// Sum of the (1 based) indices of the pairs which are in the right order.
+fn part1(pairs: &[(Packet, Packet)]) -> usize {
+    pairs
+        .iter()
+        .enumerate()
+        .filter(|(_, (left, right))| left < right)
+        .map(|(idx, _)| idx + 1)
+        .sum()
+}
 
-    if left.is_some() && left.unwrap().ordered_recursive(&right.unwrap()).good() {
-        ordered.push(pair_index);
-    }
+// Position of a divider once sorted is one more than the number of packets
+// which go before it, the first divider goes before the second one as well.
+fn part2(pairs: &[(Packet, Packet)]) -> usize {
+    let (first, second) = (Packet::divider(2), Packet::divider(6));
+    let packets = || pairs.iter().flat_map(|(left, right)| [left, right]);
+    let before_first = packets().filter(|packet| **packet < first).count();
+    let before_second = packets().filter(|packet| **packet < second).count();
 
-    println!("{:?}, {}", ordered, ordered.iter().sum::<usize>());
+    (before_first + 1) * (before_second + 2)
+}
 
-    nodes.sort_by(|a, b| {
-        match a.ordered_recursive(b) {
-            OrderingResult::Same => std::cmp::Ordering::Equal,
-            OrderingResult::Correct => std::cmp::Ordering::Less,
-            OrderingResult::Incorrect => std::cmp::Ordering::Equal,
-        }
-    });
-
-    let mut left = 0;
-    let mut right = 0;
-    for  (idx, node) in nodes.iter().enumerate() {
-        println!("{}", node.print());
-        if node.print() == "[[2]]" {
-            left = idx + 1;
-        } 
-        if node.print() == "[[6]]" {
-            right = idx + 1;
-        }
+pub fn solve() {
+    let pairs = read("inputs/13_input");
+
+    println!("{}", part1(&pairs));
+    println!("{}", part2(&pairs));
+}
+
+// Prints why every pair of the input is or isn't in the right order.
+pub fn explain_pairs() {
+    for (idx, (left, right)) in read("inputs/13_input").iter().enumerate() {
+        println!("== Pair {} ==", idx + 1);
+        println!("{}\n", left.explain(right).render(left, right));
     }
+}
 
-    println!("Decoder key {}", left * right);
+// Reads JSON packets from stdin, one per line, and pretty prints them along
+// with what's in them.
+pub fn inspect_json() {
+    for (idx, line) in io::stdin().lock().lines().enumerate() {
+        let line = match line {
+            Err(why) => panic!("Couldn't read stdin {}", why),
+            Ok(line) => line,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
 
+        match Packet::from_json_str(&line) {
+            Ok(packet) => {
+                let stats = packet.stats();
+                println!("{}", packet.pretty());
+                println!(
+                    "{} deep, {} lists, {} integers adding up to {}\n",
+                    stats.depth,
+                    stats.lists,
+                    stats.integers,
+                    packet.total()
+                );
+            }
+            Err(why) => println!("line {}: {}\n", idx + 1, why),
+        }
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn compare(left: &str, right: &str) -> Ordering {
+        Packet::parse(left).unwrap().cmp(&Packet::parse(right).unwrap())
+    }
+
     #[test]
     fn base_case_1() {
-        let left = Node::parse("[1,2,3,4]");
-        let right = Node::parse("[1,2,3,4]");
-
-        assert_eq!(OrderingResult::Same, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Equal, compare("[1,2,3,4]", "[1,2,3,4]"));
     }
 
     #[test]
     fn base_case_2() {
-        let left = Node::parse("[1,2,3]");
-        let right = Node::parse("[1,2,3,4]");
-
-        assert_eq!(OrderingResult::Same, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[1,2,3]", "[1,2,3,4]"));
     }
 
     #[test]
     fn base_case_3() {
-        let left = Node::parse("[1,2,3,4]");
-        let right = Node::parse("[1,2,3]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Greater, compare("[1,2,3,4]", "[1,2,3]"));
     }
 
     #[test]
     fn base_case_4() {
-        let left = Node::parse("[1,5,3]");
-        let right = Node::parse("[1,2,3,4]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Greater, compare("[1,5,3]", "[1,2,3,4]"));
     }
 
     #[test]
     fn complex_0() {
-        let left = Node::parse("[5,[4,1]]");
-        let right = Node::parse("[5,2,3]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Greater, compare("[5,[4,1]]", "[5,2,3]"));
     }
 
     #[test]
     fn complex_1() {
-        let left = Node::parse("[1,2,3,[4,1]]");
-        let right = Node::parse("[1,2,3,4,6]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Greater, compare("[1,2,3,[4,1]]", "[1,2,3,4,6]"));
     }
 
     #[test]
     fn complex_2() {
-        let left = Node::parse("[1,2,3,[4],1]");
-        let right = Node::parse("[1,2,3,5,6]");
-
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[1,2,3,[4],1]", "[1,2,3,5,6]"));
     }
 
     #[test]
     fn complex_3() {
-        let left = Node::parse("[1,2,3]");
-        let right = Node::parse("[1,[2],3]");
-
-        assert_eq!(OrderingResult::Same, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Equal, compare("[1,2,3]", "[1,[2],3]"));
     }
 
     #[test]
     fn complex_4() {
-        // [1,[2],3]
-        let left = Node::parse("[1,[2],3]");
-        let right = Node::parse("[1,[2,3]]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        // [2] runs out first
+        assert_eq!(Ordering::Less, compare("[1,[2],3]", "[1,[2,3]]"));
     }
 
     #[test]
     fn complex_5() {
-        // [1,[2],3]
-        let left = Node::parse("[1,2,3]");
-        let right = Node::parse("[1,[2,3]]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        // 2 becomes [2], which runs out first
+        assert_eq!(Ordering::Less, compare("[1,2,3]", "[1,[2,3]]"));
     }
 
-    // #[test]
-    // fn left_greater_than_right() {
-    //     let left = Node::simple(100);
-    //     let right = Node::simple(0);
-
-    //     assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
-    // }
-
-    // #[test]
-    // fn right_greater_than_left() {
-    //     let left = Node::simple(0);
-    //     let right = Node::simple(1);
-
-    //     assert_eq!(OrderingResult::Incorrect,  left.ordered_recursive(&right));
-    // }
-
-    // #[test]
-    // fn right_runs_out_of_the_items() {
-    //     let left = Node::simple_list(vec![1]);
-    //     let right = Node::simple_list(vec![]);
-
-    //     assert_eq!(OrderingResult::Incorrect,  left.ordered_recursive(&right));
-    // }
-
-    // #[test]
-    // fn simple_case() {
-    //     let left = Node::simple_list(vec![1]);
-    //     let right = Node::simple_list(vec![2]);
-
-    //     assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
-    // }
-
-    // #[test]
-    // fn simple_case_longer_lists() {
-    //     let left = Node::simple_list(vec![1, 1, 3, 1, 1]);
-    //     let right = Node::simple_list(vec![1, 1, 5, 1, 1]);
-
-    //     assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
-    // }
+    #[test]
+    fn integers_and_lists() {
+        assert_eq!(Ordering::Greater, compare("100", "0"));
+        assert_eq!(Ordering::Less, compare("0", "1"));
+        assert_eq!(Ordering::Greater, compare("[1]", "[]"));
+        assert_eq!(Ordering::Less, compare("[1]", "[2]"));
+        assert_eq!(Ordering::Less, compare("[1,1,3,1,1]", "[1,1,5,1,1]"));
+        assert_eq!(Ordering::Equal, compare("[[1]]", "1"));
+    }
 
     #[test]
     fn complex_case() {
-        // [[1], [2, 3, 4]]
-        // [[1], 4]
-        let left = Node::parse("[[1],[2,3,4]]");
-        let right = Node::parse("[[1],4]");
-
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[[1],[2,3,4]]", "[[1],4]"));
     }
 
     #[test]
     fn complex_case_2() {
-        // [9] vs
-        // [[8,7,6]]
-        let left = Node::parse("[9]");
-        let right = Node::parse("[[8,7,6]]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Greater, compare("[9]", "[[8,7,6]]"));
     }
 
     #[test]
     fn complex_case_woot() {
-        let left = Node::parse("[1,[2,[3,[4,[5,6,7]]]],8,9]");
-        let right = Node::parse("[1,[2,[3,[4,[5,6,0]]]],8,9]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Greater, compare("[1,[2,[3,[4,[5,6,7]]]],8,9]", "[1,[2,[3,[4,[5,6,0]]]],8,9]"));
     }
 
     #[test]
     fn complex_case_woot_2() {
-        let left = Node::parse("[[4,4],4,4]");
-        let right = Node::parse("[[4,4],4,4,4]");
-
-        assert_eq!(OrderingResult::Same, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[[4,4],4,4]", "[[4,4],4,4,4]"));
     }
 
     #[test]
     fn complex_case_woot_3() {
-        let left = Node::parse("[]");
-        let right = Node::parse("[3]");
-
-        assert_eq!(OrderingResult::Same, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[]", "[3]"));
     }
 
     #[test]
     fn complex_case_woot_4() {
-        let left = Node::parse("[[[]]]");
-        let right = Node::parse("[[]]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Greater, compare("[[[]]]", "[[]]"));
     }
 
     #[test]
     fn complex_case_woot_44() {
-        let left = Node::parse("[[4],3]");
-        let right = Node::parse("[[5],2]");
-
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[[4],3]", "[[5],2]"));
     }
 
     #[test]
     fn complex_case_woot_5() {
-        //        let left = Node::parse(" [[ ],    [0] , [[ ]] ]");
-        //        let right = Node::parse("[[0],   [[4]],       ]");
-        let left = Node::parse("[[],[0],[[]]]");
-        let right = Node::parse("[[0],[[4]]]");
-
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[[],[0],[[]]]", "[[0],[[4]]]"));
     }
 
     #[test]
     fn complex_case_woot_6() {
-        //        let left = Node::parse(" [[ ],    [0] , [[ ]] ]");
-        //        let right = Node::parse("[[0],   [[4]],       ]");
-        let left = Node::parse("[[],[1],[[[1,3],2,1,3]]]");
-        let right = Node::parse("[[[],6,[3,8]],[]]");
-
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+        assert_eq!(Ordering::Less, compare("[[],[1],[[[1,3],2,1,3]]]", "[[[],6,[3,8]],[]]"));
     }
 
     #[test]
     fn complex_12() {
-        let left = Node::parse("[[1,[[7,6,3,4],9,[]]],[6],[],[[10],[3,[7,9],[8,0,1,6,7],3,[7,8,4,5]],3],[[4,[8,1,0,7],6]]]");
-        let right = Node::parse("[[[1]],[4,5,2,[0]]]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(
+            Ordering::Greater,
+            compare(
+                "[[1,[[7,6,3,4],9,[]]],[6],[],[[10],[3,[7,9],[8,0,1,6,7],3,[7,8,4,5]],3],[[4,[8,1,0,7],6]]]",
+                "[[[1]],[4,5,2,[0]]]"
+            )
+        );
     }
 
     #[test]
     fn complex_25() {
-        let left = Node::parse("[[[9,[2,4,3]],6],[4,9],[8]]");
-        let right = Node::parse("[[10,[],7],[10],[[[1,6],[4,0],9,8],[[6,1,5,6],2],10,5],[],[[8,[8,5],[1,6,6,4,10]],5,8,6]]");
-
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+        assert_eq!(
+            Ordering::Less,
+            compare(
+                "[[[9,[2,4,3]],6],[4,9],[8]]",
+                "[[10,[],7],[10],[[[1,6],[4,0],9,8],[[6,1,5,6],2],10,5],[],[[8,[8,5],[1,6,6,4,10]],5,8,6]]"
+            )
+        );
     }
 
     #[test]
     fn complex_7() {
-        let left = Node::parse("[[2,0],[[],[[3,0,6],6,2,6],8,5,[0,[10,0,10,10,8],[4,5,1]]],[[[6,7,0,6,10],[8],[1],6,7],0,6,[10,5,4,[4,2,9],0],[[2,7,8,6,7]]],[[[7,8,6,3],0,[4,3,3,10,8],[4]],[8],2,1,[1,7,[2,3,6],[7,3],9]]]");
-        let right = Node::parse("[[6],[5],[],[6,0],[[],[9,[10,5],10,[4,3,0,6,6]]]]");
-
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+        assert_eq!(
+            Ordering::Less,
+            compare(
+                "[[2,0],[[],[[3,0,6],6,2,6],8,5,[0,[10,0,10,10,8],[4,5,1]]],[[[6,7,0,6,10],[8],[1],6,7],0,6,[10,5,4,[4,2,9],0],[[2,7,8,6,7]]],[[[7,8,6,3],0,[4,3,3,10,8],[4]],[8],2,1,[1,7,[2,3,6],[7,3],9]]]",
+                "[[6],[5],[],[6,0],[[],[9,[10,5],10,[4,3,0,6,6]]]]"
+            )
+        );
     }
 
     #[test]
     fn parse_xd() {
-        let left = Node::parse("[[2,0],3]");
+        assert_eq!("[[2,0],3]", Packet::parse("[[2,0],3]").unwrap().to_string());
+        assert_eq!("[[],[10,[]]]", "[[],[10,[]]]".parse::<Packet>().unwrap().to_string());
+    }
 
-        assert_eq!("[[2,0],3]", left.print());
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Err(ParseError::UnexpectedEnd), Packet::parse("[1,[2]"));
+        assert_eq!(
+            Err(ParseError::UnexpectedCharacter { position: 3, found: ']' }),
+            Packet::parse("[1,]")
+        );
+        assert_eq!(
+            Err(ParseError::UnexpectedCharacter { position: 0, found: 'x' }),
+            Packet::parse("x")
+        );
+        assert_eq!(Err(ParseError::TrailingInput { position: 3 }), Packet::parse("[1]]"));
+        assert_eq!(Err(ParseError::NumberTooLarge { position: 1 }), Packet::parse("[99999999999]"));
     }
 
     #[test]
     fn complex_91() {
-        let left = Node::parse("[[],[1],[3],[[[7,8,8,4],0],[7,5],[5,[2,0,5,10],[7]],10],[9,[10,7,[10,1,10,8]],5,7,0]]");
-        let right = Node::parse("[[],[],[[],10,[[6],[4,5],[2,2],[7,7]]],[[10,[8]],[[6,9],[3],8],2,[[0,9,1,3],0,5,2,3],[8,5,7,10]]]");
-
-        assert_eq!(OrderingResult::Incorrect, left.ordered_recursive(&right));
+        assert_eq!(
+            Ordering::Greater,
+            compare(
+                "[[],[1],[3],[[[7,8,8,4],0],[7,5],[5,[2,0,5,10],[7]],10],[9,[10,7,[10,1,10,8]],5,7,0]]",
+                "[[],[],[[],10,[[6],[4,5],[2,2],[7,7]]],[[10,[8]],[[6,9],[3],8],2,[[0,9,1,3],0,5,2,3],[8,5,7,10]]]"
+            )
+        );
     }
 
     #[test]
     fn complex_79() {
-        let left = Node::parse("[[[3],[4,7,1,[2,2,1,8],[1,5]],4,2],[[],3],[],[[[3,8,0,6,5],6,[0]],4]]");
-        let right = Node::parse("[[[3,0],[[0,0,10,4],[4,6,4,5,2]],[[7],7,[10,7,2],[2,6,3],6],9]]");
+        assert_eq!(
+            Ordering::Less,
+            compare(
+                "[[[3],[4,7,1,[2,2,1,8],[1,5]],4,2],[[],3],[],[[[3,8,0,6,5],6,[0]],4]]",
+                "[[[3,0],[[0,0,10,4],[4,6,4,5,2]],[[7],7,[10,7,2],[2,6,3],6],9]]"
+            )
+        );
+    }
 
-        assert_eq!(OrderingResult::Correct, left.ordered_recursive(&right));
+    #[test]
+    fn sorted_base() {
+        let mut packets: Vec<Packet> = read("inputs/13_base").into_iter().flat_map(|(left, right)| [left, right]).collect();
+        packets.extend([Packet::divider(2), Packet::divider(6)]);
+        packets.sort();
+
+        let sorted: Vec<String> = packets.iter().map(|packet| packet.to_string()).collect();
+        assert_eq!(
+            vec![
+                "[]",
+                "[[]]",
+                "[[[]]]",
+                "[1,1,3,1,1]",
+                "[1,1,5,1,1]",
+                "[[1],[2,3,4]]",
+                "[1,[2,[3,[4,[5,6,0]]]],8,9]",
+                "[1,[2,[3,[4,[5,6,7]]]],8,9]",
+                "[[1],4]",
+                "[[2]]",
+                "[3]",
+                "[[4,4],4,4]",
+                "[[4,4],4,4,4]",
+                "[[6]]",
+                "[7,7,7]",
+                "[7,7,7,7]",
+                "[[8,7,6]]",
+                "[9]",
+            ],
+            sorted
+        );
+    }
+
+    #[test]
+    fn base() {
+        let pairs = read("inputs/13_base");
+
+        assert_eq!(8, pairs.len());
+        assert_eq!(13, part1(&pairs));
+        assert_eq!(140, part2(&pairs));
+    }
+
+    #[test]
+    fn answers() {
+        let pairs = read("inputs/13_input");
+
+        assert_eq!(5588, part1(&pairs));
+        assert_eq!(23958, part2(&pairs));
+    }
+
+    fn explain(left: &str, right: &str) -> Explanation {
+        Packet::parse(left).unwrap().explain(&Packet::parse(right).unwrap())
+    }
+
+    #[test]
+    fn explanations() {
+        let explanation = explain("[1,1,3,1,1]", "[1,1,5,1,1]");
+        assert_eq!(Ordering::Less, explanation.ordering);
+        assert_eq!(
+            Some(Difference {
+                path: vec![2],
+                left: Some(Packet::Integer(3)),
+                right: Some(Packet::Integer(5)),
+                rule: Rule::IntegerCompare
+            }),
+            explanation.difference
+        );
+
+        let explanation = explain("[7,7,7,7]", "[7,7,7]");
+        assert_eq!(Ordering::Greater, explanation.ordering);
+        let difference = explanation.difference.unwrap();
+        assert_eq!((vec![3], Rule::ListLength), (difference.path, difference.rule));
+        assert_eq!("7", difference.left.unwrap().to_string());
+        assert!(difference.right.is_none());
+
+        let explanation = explain("[9]", "[[8,7,6]]");
+        assert_eq!("wrong order at [0][0]: 9 > 8 (left integer promoted to a list, then integer compare)", explanation.summary());
+
+        let explanation = explain("[[1],[2,3,4]]", "[[1],4]");
+        assert_eq!("right order at [1][0]: 2 < 4 (right integer promoted to a list, then integer compare)", explanation.summary());
+
+        assert_eq!(Explanation { ordering: Ordering::Equal, difference: None }, explain("[[1]]", "[1]"));
+    }
+
+    #[test]
+    fn explanations_agree_with_ordering() {
+        for (left, right) in read("inputs/13_input") {
+            assert_eq!(left.cmp(&right), left.explain(&right).ordering);
+        }
+    }
+
+    #[test]
+    fn render_explanation() {
+        let (left, right) = (Packet::parse("[[1],[2,3,4]]").unwrap(), Packet::parse("[[1],4]").unwrap());
+
+        assert_eq!(
+            "  left  |   right
+  [     |   [
+    [   |     [
+      1 |       1
+    ]   |     ]
+    [   | >   4
+>     2 |   ]
+      3 |
+      4 |
+    ]   |
+  ]     |
+right order at [1][0]: 2 < 4 (right integer promoted to a list, then integer compare)",
+            left.explain(&right).render(&left, &right)
+        );
+        // the list which ran out gets its closing bracket marked
+        let (left, right) = (Packet::parse("[1,[]]").unwrap(), Packet::parse("[1,[0]]").unwrap());
+        assert_eq!(
+            "  left |   right
+  [    |   [
+    1  |     1
+>   [] |     [
+  ]    | >     0
+       |     ]
+       |   ]
+right order at [1][0]: nothing < 0 (list length)",
+            left.explain(&right).render(&left, &right)
+        );
     }
-}
 
-/*
-13_base
-What are the indices of the pairs that are already in the right order?
- (The first pair has index 1, the second pair has index 2, and so on.)
- In the above example, the pairs in the right order are 1, 2, 4, and 6; the sum of these indices is 13.
-*/
+    #[test]
+    fn json_round_trip() {
+        let packet = Packet::parse("[[1],[2,[3,[]]],4]").unwrap();
+        let json = packet.to_json();
+
+        assert_eq!(serde_json::json!([[1], [2, [3, []]], 4]), json);
+        assert_eq!(packet.to_string(), Packet::from_json(&json).unwrap().to_string());
+        assert_eq!("[[1],[2,[3,[]]],4]", Packet::from_json_str(" [ [1], [2, [3, [ ]]], 4 ]\n").unwrap().to_string());
+        assert_eq!("[\n  1,\n  [\n    2\n  ]\n]", Packet::parse("[1,[2]]").unwrap().pretty());
+
+        assert_eq!(Err(JsonError::Unsupported("-1".to_string())), Packet::from_json_str("[-1]"));
+        assert_eq!(Err(JsonError::Unsupported("1.5".to_string())), Packet::from_json_str("[1.5]"));
+        assert_eq!(Err(JsonError::Unsupported("\"a\"".to_string())), Packet::from_json_str("[\"a\"]"));
+        assert_eq!(
+            Err(JsonError::Syntax("EOF while parsing a value at line 1 column 3".to_string())),
+            Packet::from_json_str("[1,")
+        );
+        assert_eq!(
+            "invalid JSON: expected value at line 1 column 2",
+            Packet::from_json_str("[x]").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn stats_and_flattening() {
+        let packet = Packet::parse("[[1],[2,[3,[]]],4]").unwrap();
+
+        assert_eq!(Stats { depth: 4, integers: 4, lists: 5 }, packet.stats());
+        assert_eq!(Stats { depth: 0, integers: 1, lists: 0 }, Packet::Integer(7).stats());
+        assert_eq!(vec![1, 2, 3, 4], packet.flatten());
+        assert!(Packet::parse("[[[]]]").unwrap().flatten().is_empty());
+        assert_eq!(10, packet.total());
+        assert_eq!(8_000_000_000, Packet::parse("[4000000000,4000000000]").unwrap().total());
+    }
+
+    #[test]
+    fn streaming_pairs() {
+        let input = "[1]\r\n[2]  \r\n\r\n\r\n[[3]]\n  [4]\n[5]\n[6]";
+        let pairs: Vec<String> = Pairs::new(input.as_bytes())
+            .map(|pair| pair.map(|(left, right)| format!("{} {}", left, right)).unwrap())
+            .collect();
+
+        assert_eq!(vec!["[1] [2]", "[[3]] [4]", "[5] [6]"], pairs);
+
+        let mut unpaired = Pairs::new("[1]\n[2]\n\n[3]\n\n".as_bytes());
+        assert!(unpaired.next().unwrap().is_ok());
+        assert!(matches!(unpaired.next(), Some(Err(ReadError::UnpairedPacket { line: 4 }))));
+        assert!(unpaired.next().is_none());
+
+        let mut broken = Pairs::new("[1]\n[2,]".as_bytes());
+        assert!(matches!(
+            broken.next(),
+            Some(Err(ReadError::Packet { line: 2, error: ParseError::UnexpectedCharacter { position: 3, found: ']' } }))
+        ));
+    }
+}
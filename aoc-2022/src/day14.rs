@@ -1,5 +1,6 @@
-use std::cmp::{min, max};
-use std::{collections::HashMap, ops::RangeInclusive, fs::File};
+use std::cmp::{max, min};
+use std::fmt;
+use std::{fs, fs::File, ops::RangeInclusive};
 use std::io::{self, BufRead};
 
 use regex::Regex;
@@ -9,14 +10,25 @@ enum TileType {
     Air,
     Rock,
     Sand,
+    Water,
     Source,
 }
 
+const SOURCE: (i32, i32) = (500, 0);
+
+// Tiles live in a dense array which grows to fit whatever gets inserted,
+// anything outside of it is air, apart from the floor in the floored variant.
 struct Grid {
-    tiles: HashMap<(i32, i32), TileType>,
+    tiles: Vec<TileType>,
+    // x of the first column
+    origin_x: i32,
+    width: usize,
+    height: usize,
     max_tile_y: i32,
+    // columns anything but air has been put in, min > max while there's none
     min_tile_x: i32,
     max_tile_x: i32,
+    floor: Option<i32>,
 }
 
 struct Line {
@@ -55,46 +67,82 @@ fn range(a: i32, b: i32) -> RangeInclusive<i32> {
 }
 
 impl Grid {
+    // The puzzle's cave, with the source at 500,0.
     fn new() -> Grid {
-        let mut grid = Grid {
-            tiles: HashMap::new(),
+        let mut grid = Grid::empty();
+        grid.insert_tile(SOURCE, TileType::Source);
+        grid
+    }
+
+    fn empty() -> Grid {
+        Grid {
+            tiles: Vec::new(),
+            origin_x: 0,
+            width: 0,
+            height: 0,
             max_tile_y: 0,
-            min_tile_x: 500,
-            max_tile_x: 500
+            min_tile_x: i32::MAX,
+            max_tile_x: i32::MIN,
+            floor: None,
+        }
+    }
+
+    fn index(&self, pos: (i32, i32)) -> Option<usize> {
+        let column = pos.0 - self.origin_x;
+        if column < 0 || column as usize >= self.width || pos.1 < 0 || pos.1 as usize >= self.height {
+            return None;
+        }
+
+        Some(pos.1 as usize * self.width + column as usize)
+    }
+
+    // Makes sure the columns from `min_x` to `max_x` and the rows down to
+    // `max_y` are stored, copying everything over if they aren't yet.
+    fn reserve(&mut self, min_x: i32, max_x: i32, max_y: i32) {
+        let (min_x, max_x) = match self.width {
+            0 => (min_x, max_x),
+            width => (min(min_x, self.origin_x), max(max_x, self.origin_x + width as i32 - 1)),
         };
+        let width = (max_x - min_x + 1) as usize;
+        let height = max(max_y + 1, self.height as i32) as usize;
+        if min_x == self.origin_x && width == self.width && height == self.height {
+            return;
+        }
 
-        grid.tiles.insert((500, 0), TileType::Source);
-        grid
+        let mut tiles = vec![TileType::Air; width * height];
+        for y in 0..self.height {
+            let offset = (self.origin_x - min_x) as usize;
+            tiles[y * width + offset..y * width + offset + self.width]
+                .copy_from_slice(&self.tiles[y * self.width..(y + 1) * self.width]);
+        }
+
+        self.tiles = tiles;
+        self.origin_x = min_x;
+        self.width = width;
+        self.height = height;
     }
 
     fn insert_tile(&mut self, pos: (i32, i32), tile: TileType) {
         if tile == TileType::Rock {
             self.max_tile_y = max(self.max_tile_y, pos.1);
         }
-        if tile == TileType::Sand || tile == TileType::Rock {
+        if tile != TileType::Air {
             self.min_tile_x = min(self.min_tile_x, pos.0);
             self.max_tile_x = max(self.max_tile_x, pos.0);
         }
 
-        self.tiles.insert(pos, tile);
-    }
-
-    fn clean_tile(&mut self, pos: (i32, i32)) {
-        // Source can't be cleaned
-        self.tiles.insert(pos, TileType::Air);
+        if self.index(pos).is_none() {
+            self.reserve(pos.0, pos.0, pos.1);
+        }
+        let index = self.index(pos).unwrap();
+        self.tiles[index] = tile;
     }
 
     // 503,4 -> 502,4 -> 502,9 -> 494,9
     fn insert_lines(&mut self, lines: Vec<Line>) {
-        let mut current_line = lines.first().unwrap();
-        for idx in 1..lines.len() {
-            let next_line = lines.get(idx).unwrap();
-
-            // 503, 4 -> 503, 5
-            // 503, 4 -> 503, 6
-            // 1..5
-            // 5..1
-            // join horizontal
+        for pair in lines.windows(2) {
+            let (current_line, next_line) = (&pair[0], &pair[1]);
+
             if current_line.x == next_line.x {
                 for y in range(current_line.y, next_line.y) {
                     self.insert_tile((current_line.x, y), TileType::Rock);
@@ -104,149 +152,411 @@ impl Grid {
                     self.insert_tile((x, current_line.y), TileType::Rock)
                 }
             } else {
-                panic!("Shouldn't happen.");
+                panic!("Rock paths have to be horizontal or vertical.");
             }
-
-            current_line = next_line;
         }
     }
 
+    fn puzzle_floor(&self) -> i32 {
+        self.max_tile_y + 2
+    }
+
+    // Everything from the floor down is solid, even where there's rock
+    // stored below it.
     fn get_tile(&self, pos: &(i32, i32)) -> &TileType {
-        match self.tiles.get(&pos) {
-            Some(tile) => tile,
-            None => {
-                if pos.1 == self.max_tile_y + 2 {
-                    &TileType::Rock
-                } else {
-                    &TileType::Air
-                }
-            },
+        if self.floor.is_some_and(|floor| pos.1 >= floor) {
+            return &TileType::Rock;
+        }
+        match self.index(*pos) {
+            Some(index) => &self.tiles[index],
+            None => &TileType::Air,
         }
     }
 
-    fn drop_sand(&mut self) -> (SandState, (i32, i32)) {
-        let mut current_pos = (500, 0);
-
-        let mut moved;
-        let mut last_state = SandState::Moved;
-
-        while last_state == SandState::Moved {
-            moved = false;
-            for next_possible_pos in self.next_possible_positions(current_pos) {
-                match self.get_tile(&next_possible_pos) {
-                    TileType::Air => {
-                        self.clean_tile(current_pos);
-                        self.insert_tile(next_possible_pos, TileType::Sand);
-                        current_pos = next_possible_pos;
-                        moved = true;
-                        break;
-                    },
-                    TileType::Rock => continue,
-                    TileType::Sand => continue,
-                    TileType::Source => panic!("Can't happen"),
-                }
+
+    // Drops particles until every source is buried or loses one to the
+    // abyss (or over the edge of the world), returns how many came to rest.
+    // Sources take turns. A particle follows the previous one from its
+    // source up to the point where that one came to rest, so the path it
+    // took is kept and every particle starts from the last position on it
+    // which is still free. Particles never go back to a cell on their own
+    // path, that keeps water from sloshing back and forth forever.
+    fn pour(&mut self, physics: &Physics) -> usize {
+        self.floor = match physics.bottom {
+            Bottom::Floor(depth) => Some(depth),
+            Bottom::Abyss => None,
+        };
+        let bottom = self.floor.unwrap_or(self.max_tile_y + 1);
+        let top = physics.sources.iter().map(|source| source.1).min().unwrap_or(0);
+        // falling sand can't get further sideways than it falls, the world
+        // ends there for anything else too
+        let reach = bottom - top;
+        let min_x = physics.sources.iter().map(|source| source.0 - reach).fold(self.min_tile_x, min) - 1;
+        let max_x = physics.sources.iter().map(|source| source.0 + reach).fold(self.max_tile_x, max) + 1;
+        self.reserve(min_x, max_x, bottom - 1);
+        for source in &physics.sources {
+            if *self.get_tile(source) == TileType::Air {
+                self.insert_tile(*source, TileType::Source);
             }
-            
-            last_state = if current_pos.1 >= self.max_tile_y + 2 {
-                SandState::Abyss
-            } else if moved {
-                SandState::Moved
-            } else {
-                SandState::Settled
+        }
+
+        let mut on_path = vec![false; self.tiles.len()];
+        let mut paths: Vec<Vec<(i32, i32)>> = vec![Vec::new(); physics.sources.len()];
+        let mut active = vec![true; physics.sources.len()];
+        let mut settled = 0;
+
+        while active.contains(&true) {
+            for (idx, source) in physics.sources.iter().enumerate() {
+                if !active[idx] {
+                    continue;
+                }
+
+                let path = &mut paths[idx];
+                if physics.sources.len() > 1 {
+                    // other sources may have filled some of it
+                    if let Some(blocked) = path.iter().position(|pos| !self.is_passable(*pos)) {
+                        path.truncate(blocked);
+                    }
+                    on_path.iter_mut().for_each(|cell| *cell = false);
+                    for pos in path.iter() {
+                        on_path[self.index(*pos).unwrap()] = true;
+                    }
+                }
+                if path.is_empty() {
+                    if !self.is_passable(*source) {
+                        active[idx] = false;
+                        continue;
+                    }
+                    path.push(*source);
+                    on_path[self.index(*source).unwrap()] = true;
+                }
+
+                match self.drop_particle(physics, path, &mut on_path) {
+                    true => settled += 1,
+                    false => active[idx] = false,
+                }
             }
         }
 
-        (last_state, current_pos)
+        settled
     }
 
-    fn next_possible_positions(&self, current_pos: (i32, i32)) -> Vec<(i32, i32)> {
-        vec![
-            // DOWN
-            (current_pos.0, current_pos.1 + 1),
-            // LEFT DOWN
-            (current_pos.0 - 1, current_pos.1 + 1),
-            // RIGHT DOWN
-            (current_pos.0 + 1, current_pos.1 + 1)
-        ]
+    // Free for a moving particle, sources included.
+    fn is_passable(&self, pos: (i32, i32)) -> bool {
+        matches!(self.get_tile(&pos), TileType::Air | TileType::Source)
     }
 
-    fn part1(&mut self) -> usize {
-        let mut finished_sands = 0;
-        loop {
-            let (state, _) = self.drop_sand();
-            if state == SandState::Abyss {
-                break;
-            } else if state == SandState::Moved {
-                panic!("shouldn't happen");
+    // Moves a particle along from the end of the path, false if it got lost.
+    fn drop_particle(&mut self, physics: &Physics, path: &mut Vec<(i32, i32)>, on_path: &mut [bool]) -> bool {
+        // past this nothing is going to stop it
+        let bottom = self.floor.unwrap_or(self.max_tile_y + 1);
+        while let Some(&current_pos) = path.last() {
+            let index = match self.index(current_pos) {
+                Some(index) if current_pos.1 < bottom => index,
+                _ => {
+                    // nothing left to land on
+                    path.pop();
+                    return false;
+                }
+            };
+
+            let next = physics
+                .fall
+                .iter()
+                .map(|(dx, dy)| (current_pos.0 + dx, current_pos.1 + dy))
+                .find(|pos| {
+                    *self.get_tile(pos) == TileType::Air && !self.index(*pos).is_some_and(|index| on_path[index])
+                });
+            match next {
+                Some(next) => {
+                    if let Some(index) = self.index(next) {
+                        on_path[index] = true;
+                    }
+                    path.push(next);
+                }
+                None => {
+                    self.insert_tile(current_pos, physics.particle.tile());
+                    on_path[index] = false;
+                    path.pop();
+                    return true;
+                }
             }
-            
-            finished_sands += 1;
         }
 
-        finished_sands
+        false
+    }
+
+    fn part1(&mut self) -> usize {
+        self.pour(&Physics::puzzle(Bottom::Abyss))
     }
 
     fn part2(&mut self) -> usize {
-        let mut finished_sands = 0;
-        loop {
-            let (state, pos) = self.drop_sand();
-            // self.print();
-            // println!();
-            if state == SandState::Settled && pos.0 == 500 && pos.1 == 0 {
-                println!("Settled at the source.");
-                break;
-            } else if state != SandState::Settled {
-                panic!("shouldn't happen");
+        let floor = self.puzzle_floor();
+        self.pour(&Physics::puzzle(Bottom::Floor(floor)))
+    }
+
+    // Sand in the floored variant ends up in every cell it can reach: the
+    // source and any free cell with a reachable one right above it or above
+    // on either side. Works on an empty grid, the simulation isn't needed.
+    #[cfg(test)]
+    fn flood_fill(&self) -> usize {
+        let depth = self.puzzle_floor();
+        let min_x = SOURCE.0 - depth;
+        let mut reachable = vec![false; (2 * depth + 1) as usize];
+        reachable[(SOURCE.0 - min_x) as usize] = true;
+        let mut count = 1;
+
+        for y in SOURCE.1 + 1..depth {
+            let above = reachable.clone();
+            for (column, cell) in reachable.iter_mut().enumerate() {
+                let from_above = above[column.saturating_sub(1)..=min(column + 1, above.len() - 1)]
+                    .iter()
+                    .any(|reached| *reached);
+                *cell = from_above && *self.get_tile(&(min_x + column as i32, y)) != TileType::Rock;
+                if *cell {
+                    count += 1;
+                }
             }
-            
-            finished_sands += 1;
         }
 
-        finished_sands + 1
+        count
     }
 
-    fn print(&self) {
-        for y in 0..=self.max_tile_y + 2 {
-            for x in self.min_tile_x - 5..=self.max_tile_x + 5 {
-                match self.get_tile(&(x, y)) {
-                    TileType::Air => print!("."),
-                    TileType::Rock => print!("#"),
-                    TileType::Sand => print!("o"),
-                    TileType::Source => print!("+"),
-                }
+    fn render(&self) -> String {
+        if self.min_tile_x > self.max_tile_x {
+            return String::new();
+        }
+
+        let mut lines = Vec::new();
+        for y in 0..=self.floor.unwrap_or(self.puzzle_floor()) {
+            let mut line = String::new();
+            for x in self.min_tile_x - 1..=self.max_tile_x + 1 {
+                line.push(match self.get_tile(&(x, y)) {
+                    TileType::Air => '.',
+                    TileType::Rock => '#',
+                    TileType::Sand => 'o',
+                    TileType::Water => '~',
+                    TileType::Source => '+',
+                });
             }
-            println!();
+            lines.push(line);
         }
+
+        lines.join("\n")
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Particle {
+    Sand,
+    // flows sideways when it can't go down
+    Water,
+}
+
+impl Particle {
+    fn tile(&self) -> TileType {
+        match self {
+            Particle::Sand => TileType::Sand,
+            Particle::Water => TileType::Water,
+        }
+    }
+
+    // Offsets a particle tries to move by, in order.
+    fn fall(&self) -> Vec<(i32, i32)> {
+        match self {
+            Particle::Sand => vec![(0, 1), (-1, 1), (1, 1)],
+            Particle::Water => vec![(0, 1), (-1, 0), (1, 0)],
+        }
+    }
+}
 
-#[derive(PartialEq, Eq, Debug)]
-enum SandState {
-    Moved,
-    Settled,
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Bottom {
+    // whatever falls below the lowest rock is lost
     Abyss,
+    // an endless floor at this depth
+    Floor(i32),
 }
 
-pub fn solve() {
-    let file = match File::open("inputs/14_input") {
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Physics {
+    sources: Vec<(i32, i32)>,
+    bottom: Bottom,
+    particle: Particle,
+    fall: Vec<(i32, i32)>,
+}
+
+impl Physics {
+    fn puzzle(bottom: Bottom) -> Physics {
+        Physics {
+            sources: vec![SOURCE],
+            bottom,
+            particle: Particle::Sand,
+            fall: Particle::Sand.fall(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ScenarioError {
+    // line numbers start at 1
+    UnknownDirective { line: usize, directive: String },
+    InvalidValue { line: usize, value: String },
+    InvalidRockPath { line: usize },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::UnknownDirective { line, directive } => {
+                write!(f, "line {}: unknown directive {}", line, directive)
+            }
+            ScenarioError::InvalidValue { line, value } => write!(f, "line {}: invalid value {}", line, value),
+            ScenarioError::InvalidRockPath { line } => {
+                write!(f, "line {}: rock paths look like 498,4 -> 498,6 and only go across or down", line)
+            }
+        }
+    }
+}
+
+// Rock paths the same as in the puzzle input, along with lines setting up
+// the physics, `#` starts a comment:
+//
+//     source 500,0
+//     floor 11          (or `floor none` for the abyss)
+//     particle water    (or `sand`, also picks its fall order)
+//     fall 0,1 -1,0 1,0
+//
+// Without any `source` lines the puzzle's one is used.
+struct Scenario {
+    grid: Grid,
+    physics: Physics,
+}
+
+impl Scenario {
+    fn parse(text: &str) -> Result<Scenario, ScenarioError> {
+        let rock_path = Regex::new(r"^\d+,\d+( -> \d+,\d+)*$").unwrap();
+        let mut grid = Grid::empty();
+        let mut physics = Physics::puzzle(Bottom::Abyss);
+        let mut sources = Vec::new();
+        let mut fall = None;
+
+        for (idx, line) in text.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if rock_path.is_match(line) {
+                let lines = Line::parse(line);
+                if lines.windows(2).any(|pair| pair[0].x != pair[1].x && pair[0].y != pair[1].y) {
+                    // diagonal rock
+                    return Err(ScenarioError::InvalidRockPath { line: line_number });
+                }
+                grid.insert_lines(lines);
+                continue;
+            }
+
+            let invalid = |value: &str| ScenarioError::InvalidValue {
+                line: line_number,
+                value: value.to_string(),
+            };
+            let point = |value: &str| -> Result<(i32, i32), ScenarioError> {
+                let (x, y) = value.split_once(',').ok_or(invalid(value))?;
+                Ok((
+                    x.trim().parse().map_err(|_| invalid(value))?,
+                    y.trim().parse().map_err(|_| invalid(value))?,
+                ))
+            };
+
+            let mut words = line.split_whitespace();
+            let directive = words.next().unwrap();
+            let values: Vec<&str> = words.collect();
+            match (directive, values.as_slice()) {
+                ("source", [value]) => match point(value)? {
+                    // the cave starts at 0,0, there's nothing to the left or above
+                    (x, y) if x < 0 || y < 0 => return Err(invalid(value)),
+                    source => sources.push(source),
+                },
+                ("floor", ["none"]) => physics.bottom = Bottom::Abyss,
+                ("floor", [value]) => match value.parse::<i32>() {
+                    Ok(floor) if floor >= 0 => physics.bottom = Bottom::Floor(floor),
+                    _ => return Err(invalid(value)),
+                },
+                ("particle", ["sand"]) => physics.particle = Particle::Sand,
+                ("particle", ["water"]) => physics.particle = Particle::Water,
+                ("fall", offsets) if !offsets.is_empty() => {
+                    fall = Some(offsets.iter().map(|value| point(value)).collect::<Result<Vec<(i32, i32)>, ScenarioError>>()?)
+                }
+                ("source" | "floor" | "particle" | "fall", _) => return Err(invalid(&values.join(" "))),
+                (directive, _) if directive.starts_with(|c: char| c.is_ascii_digit()) => {
+                    return Err(ScenarioError::InvalidRockPath { line: line_number })
+                }
+                (directive, _) => {
+                    return Err(ScenarioError::UnknownDirective {
+                        line: line_number,
+                        directive: directive.to_string(),
+                    })
+                }
+            }
+        }
+
+        if !sources.is_empty() {
+            physics.sources = sources;
+        }
+        physics.fall = fall.unwrap_or(physics.particle.fall());
+
+        Ok(Scenario { grid, physics })
+    }
+
+    fn load(filename: &str) -> Scenario {
+        let text = match fs::read_to_string(filename) {
+            Err(why) => panic!("Couldn't open file {}", why),
+            Ok(text) => text,
+        };
+
+        match Scenario::parse(&text) {
+            Err(why) => panic!("Couldn't read scenario {}: {}", filename, why),
+            Ok(scenario) => scenario,
+        }
+    }
+
+    fn run(&mut self) -> usize {
+        self.grid.pour(&self.physics)
+    }
+}
+
+fn load(filename: &str) -> Grid {
+    let file = match File::open(filename) {
         Err(why) => panic!("Couldn't open file {}", why),
         Ok(file) => file,
     };
 
-    let reader = io::BufReader::new(file).lines();
-
     let mut grid = Grid::new();
-    for line in reader {
-        if let Ok(line) = line {
-            grid.insert_lines(Line::parse(line.as_str()))
+    for line in io::BufReader::new(file).lines().map_while(Result::ok) {
+        if !line.trim().is_empty() {
+            grid.insert_lines(Line::parse(&line));
         }
     }
 
-    let p2 = grid.part2();
-    println!("Part2: {}", p2);
+    grid
+}
+
+pub fn solve() {
+    println!("{}", load("inputs/14_input").part1());
+    println!("{}", load("inputs/14_input").part2());
 }
 
+// Runs a scenario file (see `Scenario`) and shows where everything came to rest.
+pub fn run_scenario(filename: &str) {
+    let mut scenario = Scenario::load(filename);
+    let count = scenario.run();
+
+    println!("{}", scenario.grid.render());
+    println!("{} units came to rest", count);
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -341,4 +651,165 @@ mod tests {
         let sands = grid.part2();
         assert_eq!(93, sands)
     }
+
+    #[test]
+    fn resumed_path() {
+        let mut grid = load("inputs/14_base");
+
+        assert_eq!(24, grid.part1());
+        assert_eq!(
+            ".......+....
+............
+.......o....
+......ooo...
+.....#ooo##.
+....o#ooo#..
+...###ooo#..
+.....oooo#..
+..o.ooooo#..
+.#########..
+............
+............",
+            grid.render()
+        );
+        // the grains from part 1 stay where they are
+        assert_eq!(93 - 24, grid.part2());
+    }
+
+    #[test]
+    fn flood_fill_matches_simulation() {
+        for filename in ["inputs/14_base", "inputs/14_input"] {
+            let grid = load(filename);
+            assert_eq!(load(filename).part2(), grid.flood_fill(), "{}", filename);
+        }
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(665, load("inputs/14_input").part1());
+        assert_eq!(25434, load("inputs/14_input").part2());
+    }
+
+    #[test]
+    fn custom_floor() {
+        let mut scenario = Scenario::parse("floor 3").unwrap();
+
+        assert_eq!(9, scenario.run());
+        assert_eq!("...o...\n..ooo..\n.ooooo.\n#######", scenario.grid.render());
+    }
+
+    #[test]
+    fn floor_above_rocks() {
+        let mut scenario = Scenario::parse("floor 3\n490,9 -> 510,9").unwrap();
+
+        // the rock under the floor doesn't matter
+        assert_eq!(9, scenario.run());
+        assert_eq!(TileType::Rock, *scenario.grid.get_tile(&(500, 3)));
+    }
+
+    #[test]
+    fn many_sources() {
+        let mut scenario = Scenario::parse("source 500,0\nsource 502,0\nfloor 2").unwrap();
+
+        // the grains meet in the middle and bury both sources
+        assert_eq!(7, scenario.run());
+        assert_eq!(TileType::Air, *scenario.grid.get_tile(&(501, 0)));
+    }
+
+    #[test]
+    fn fall_order() {
+        let mut scenario = Scenario::parse("floor 3\nfall 0,1 1,1").unwrap();
+
+        assert_eq!(6, scenario.run());
+        assert_eq!(TileType::Air, *scenario.grid.get_tile(&(499, 2)));
+        assert_eq!(TileType::Sand, *scenario.grid.get_tile(&(502, 2)));
+    }
+
+    #[test]
+    fn water_fills_basin() {
+        let mut scenario = Scenario::load("inputs/14_water");
+
+        assert_eq!(Particle::Water, scenario.physics.particle);
+        assert_eq!(Bottom::Abyss, scenario.physics.bottom);
+        // three rows between the walls, then it spills over the side
+        assert_eq!(27, scenario.run());
+        assert_eq!(TileType::Water, *scenario.grid.get_tile(&(496, 3)));
+        assert_eq!(TileType::Air, *scenario.grid.get_tile(&(500, 2)));
+    }
+
+    #[test]
+    fn render_away_from_the_puzzle_source() {
+        let mut scenario = Scenario::parse("source 10,0\nfloor 2").unwrap();
+
+        assert_eq!(4, scenario.run());
+        // only the columns around the scenario, nothing stretched out to 500
+        assert_eq!("..o..\n.ooo.\n#####", scenario.grid.render());
+    }
+
+    #[test]
+    fn render_water_basin() {
+        let mut scenario = Scenario::parse("particle water\nsource 10,0\nfloor none\n8,1 -> 8,2 -> 12,2 -> 12,1").unwrap();
+
+        assert_eq!(3, scenario.run());
+        assert_eq!(
+            "...+...\n.#~~~#.\n.#####.\n.......\n.......",
+            scenario.grid.render()
+        );
+    }
+
+    #[test]
+    fn puzzle_as_scenario() {
+        let text = "# the example\nsource 500,0 # same as the puzzle\nfloor none\n\n498,4 -> 498,6 -> 496,6\n503,4 -> 502,4 -> 502,9 -> 494,9";
+        let mut scenario = Scenario::parse(text).unwrap();
+
+        assert_eq!(Physics::puzzle(Bottom::Abyss), scenario.physics);
+        assert_eq!(24, scenario.run());
+    }
+
+    #[test]
+    fn scenario_errors() {
+        assert_eq!(
+            Some(ScenarioError::UnknownDirective {
+                line: 2,
+                directive: "teleport".to_string()
+            }),
+            Scenario::parse("floor 3\nteleport 1,2").err()
+        );
+        assert_eq!(
+            Some(ScenarioError::InvalidValue {
+                line: 1,
+                value: "deep".to_string()
+            }),
+            Scenario::parse("floor deep").err()
+        );
+        assert_eq!(
+            Some(ScenarioError::InvalidValue {
+                line: 1,
+                value: "1".to_string()
+            }),
+            Scenario::parse("fall 0,1 1").err()
+        );
+        assert_eq!(
+            Some(ScenarioError::InvalidRockPath { line: 3 }),
+            Scenario::parse("\n# rocks\n498,4 -> 498").err()
+        );
+        assert_eq!(
+            Some(ScenarioError::InvalidRockPath { line: 2 }),
+            Scenario::parse("498,4 -> 498,6\n1,1 -> 2,2").err()
+        );
+        assert_eq!(
+            Some(ScenarioError::InvalidValue {
+                line: 1,
+                value: "500,-2".to_string()
+            }),
+            Scenario::parse("source 500,-2").err()
+        );
+        assert_eq!(
+            Some(ScenarioError::InvalidValue {
+                line: 2,
+                value: "-1".to_string()
+            }),
+            Scenario::parse("source 500,0\nfloor -1").err()
+        );
+    }
 }
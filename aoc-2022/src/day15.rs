@@ -5,28 +5,35 @@
 //      if dist(position, sensor) <= dist(sensor, closest_beacon)
 //          cant_be = true;
 
-use std::cmp::{min, max};
+use std::cmp::{max, min};
 use std::collections::HashSet;
-use std::{fs::File};
+use std::{fs::File, ops::RangeInclusive};
 use std::io::{self, BufRead};
 use regex::Regex;
 
-#[derive(Debug, Clone, Copy)]
+// The puzzle's coordinates go up to a few million and the tuning frequency
+// multiplies them by four million, too much for an i32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Position {
-    x: i32,
-    y: i32,
+    x: i64,
+    y: i64,
 }
 
 impl Position {
-    fn new(x: i32, y: i32) -> Position {
+    fn new(x: i64, y: i64) -> Position {
         Position { x, y }
     }
 
-    fn manhattan(&self, other: &Position) -> i32 {
-        (self.x.abs_diff(other.x) + self.y.abs_diff(other.y)).try_into().unwrap()
+    fn manhattan(&self, other: &Position) -> i64 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
     }
 
-    fn to_tuple(&self) -> (i32, i32) {
+    // None if it doesn't fit.
+    fn tuning_frequency(&self) -> Option<i64> {
+        self.x.checked_mul(4000000)?.checked_add(self.y)
+    }
+
+    fn to_tuple(self) -> (i64, i64) {
         (self.x, self.y)
     }
 }
@@ -40,206 +47,192 @@ struct Beacon {
     position: Position,
 }
 
+// A rectangle of cells, both ranges inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Region {
+    x: RangeInclusive<i64>,
+    y: RangeInclusive<i64>,
+}
+
+impl Region {
+    // The puzzle's search area, 0 to `max` in both directions.
+    fn square(max: i64) -> Region {
+        Region { x: 0..=max, y: 0..=max }
+    }
+}
+
 struct Map {
     sensors: Vec<Sensor>,
-    beacons: HashSet<(i32, i32)>,
-    min_x_of_any_sensor: i32,
-    max_x_of_any_sensor: i32,
+    beacons: HashSet<(i64, i64)>,
 }
 
 impl Map {
     fn new() -> Map {
         Map {
             sensors: vec![],
-            min_x_of_any_sensor: 0,
-            max_x_of_any_sensor: 0,
             beacons: HashSet::new()
         }
     }
 
     fn add_sensor(&mut self, sensor: Sensor) {
-        let distance_to_nearest = sensor.position.manhattan(&sensor.closest_beacon.position);
-
         self.beacons.insert(sensor.closest_beacon.position.to_tuple());
-
-        self.min_x_of_any_sensor = min(self.min_x_of_any_sensor, sensor.position.x - distance_to_nearest);
-        self.max_x_of_any_sensor = max(self.max_x_of_any_sensor, sensor.position.x + distance_to_nearest);
-
         self.sensors.push(sensor);
     }
 
     // is reachable by any sensor
     fn is_reachable_by_any_sensor(&self, position: &Position) -> bool {
-        for sensor in self.sensors.iter() {
-            if position.manhattan(&sensor.position) <= sensor.position.manhattan(&sensor.closest_beacon.position) {
-                return true;
-            }
-        }
-
-        return false;
+        self.sensors.iter().any(|sensor| sensor.covers(position))
     }
 
-    fn unavailable_beacon_positions(&self, y: i32) -> usize {
-        let mut unavailable_positions = 0;
-        println!("Scanning from: {} to: {}", self.min_x_of_any_sensor, self.max_x_of_any_sensor);
-        for x in self.min_x_of_any_sensor..=self.max_x_of_any_sensor {
-            let current_position = Position::new(x, y);
-
-            let mut cant_be = false;
-
-            if self.beacons.contains(&current_position.to_tuple()) {
-                cant_be = false;
-            } else {
-                if self.is_reachable_by_any_sensor(&current_position) {
-                    cant_be = true;
+    // The stretches of row `y` some sensor can see, sorted and merged so
+    // none of them overlap or touch.
+    fn covered(&self, y: i64) -> Vec<RangeInclusive<i64>> {
+        let mut intervals: Vec<RangeInclusive<i64>> = self
+            .sensors
+            .iter()
+            .filter_map(|sensor| {
+                let reach = sensor.radius() - (sensor.position.y - y).abs();
+                (reach >= 0).then(|| sensor.position.x - reach..=sensor.position.x + reach)
+            })
+            .collect();
+        intervals.sort_by_key(|interval| *interval.start());
+
+        let mut merged: Vec<RangeInclusive<i64>> = Vec::new();
+        for interval in intervals {
+            match merged.last_mut() {
+                Some(last) if *interval.start() <= *last.end() + 1 => {
+                    *last = *last.start()..=max(*last.end(), *interval.end());
                 }
-            }
-
-            if cant_be {
-                // println!("Can't be at: {:?}", current_position);
-                unavailable_positions += 1;
+                _ => merged.push(interval),
             }
         }
 
-        unavailable_positions
+        merged
     }
 
+    fn unavailable_beacon_positions(&self, y: i64) -> usize {
+        let covered = self.covered(y);
+        let cells: usize = covered.iter().map(|interval| (interval.end() - interval.start()) as usize + 1).sum();
+        // a beacon that's already known is where a beacon can be
+        let beacons = self
+            .beacons
+            .iter()
+            .filter(|(x, beacon_y)| *beacon_y == y && covered.iter().any(|interval| interval.contains(x)))
+            .count();
+
+        cells - beacons
+    }
 
-    // this won't work, need to scale it.
-    fn can_be(&self, max: i32) -> Position {
-        println!("Scanning from: {} to: {}", 0, max);
-
-
-        for (idx, sensor) in self.sensors.iter().enumerate() {
-            let distance = sensor.position.manhattan(&sensor.closest_beacon.position);
-
-            let right_edge = Position::new(sensor.position.x + distance + 1, sensor.position.y);
-            let bottom_edge = Position::new(sensor.position.x, sensor.position.y + distance + 1);
-
-            let left_edge = Position::new(sensor.position.x - distance - 1, sensor.position.y);
-            let top_edge = Position::new(sensor.position.x, sensor.position.y - distance - 1);
-
-            let mut starting_position = right_edge.clone();
-            // Scans bottom-left in the right direction
-            while starting_position.x != bottom_edge.x && starting_position.y != bottom_edge.y {
-                for x in starting_position.x..=max {
-                    let current_position = Position::new(x, starting_position.y);
-    
-                    if self.beacons.contains(&current_position.to_tuple()) {
-                        continue;
-                    }
-    
-                    if self.is_reachable_by_any_sensor(&current_position) {
-                        // should we break here?
-                        break;
-                    }
-    
-                    return current_position.clone();
+    // The uncovered stretches of every row in the region, top to bottom.
+    fn gaps<'a>(&'a self, region: &'a Region) -> impl Iterator<Item = (i64, RangeInclusive<i64>)> + 'a {
+        region.y.clone().flat_map(move |y| {
+            let mut gaps = Vec::new();
+            let mut next = *region.x.start();
+            for interval in self.covered(y) {
+                if *interval.start() > next {
+                    gaps.push((y, next..=min(*interval.start() - 1, *region.x.end())));
                 }
-
-        
-                starting_position.x -= 1;
-                starting_position.y += 1;
-                if starting_position.x < 0 || starting_position.x > max || starting_position.y < 0 || starting_position.y > max {
+                next = max(next, *interval.end() + 1);
+                if next > *region.x.end() {
                     break;
                 }
-                // println!("Sensor: [{}], Down-left, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
             }
-            println!("Sensor: [{}], Down-left, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
+            if next <= *region.x.end() {
+                gaps.push((y, next..=*region.x.end()));
+            }
+            gaps
+        })
+    }
 
+    // Every cell in the region no sensor can see, known beacons included.
+    fn uncovered(&self, region: &Region) -> Vec<Position> {
+        self.gaps(region)
+            .flat_map(|(y, xs)| xs.map(move |x| Position::new(x, y)))
+            .collect()
+    }
 
-            starting_position = right_edge.clone();
-            // Scans top-left, in the right direction
-            while starting_position.x != top_edge.x && starting_position.y != top_edge.y {
-                for x in starting_position.x..=max {
-                    let current_position = Position::new(x, starting_position.y);
-    
-                    if self.beacons.contains(&current_position.to_tuple()) {
-                        continue;
-                    }
-    
-                    if self.is_reachable_by_any_sensor(&current_position) {
-                        // should we break here?
-                        break;
-                    }
-    
-                    return current_position.clone();
-                }
+    // How many cells in the region some sensor can see.
+    fn covered_area(&self, region: &Region) -> u64 {
+        region
+            .y
+            .clone()
+            .map(|y| {
+                self.covered(y)
+                    .iter()
+                    .map(|interval| {
+                        let start = max(*interval.start(), *region.x.start());
+                        let end = min(*interval.end(), *region.x.end());
+                        max(end - start + 1, 0) as u64
+                    })
+                    .sum::<u64>()
+            })
+            .sum()
+    }
 
-        
-                starting_position.x -= 1;
-                starting_position.y -= 1;
-                if starting_position.x < 0 || starting_position.x > max || starting_position.y < 0 || starting_position.y > max {
-                    break;
-                }
-                // println!("Sensor: [{}], Up-left, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
-            }
-            println!("Sensor: [{}], Up-left, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
+    fn sensors_covering(&self, position: &Position) -> Vec<&Sensor> {
+        self.sensors.iter().filter(|sensor| sensor.covers(position)).collect()
+    }
 
-            starting_position = left_edge.clone();
-            // Scans up-right, in the left direction
-            while starting_position.x != top_edge.x && starting_position.y != top_edge.y {
-                for x in (0..=starting_position.x).rev() {
-                    let current_position = Position::new(x, starting_position.y);
-    
-                    if self.beacons.contains(&current_position.to_tuple()) {
-                        continue;
-                    }
-    
-                    if self.is_reachable_by_any_sensor(&current_position) {
-                        // should we break here?
-                        break;
-                    }
-    
-                    return current_position.clone();
-                }
+    // The tuning frequency of every uncovered cell in the region, None for
+    // the ones that don't fit.
+    fn tuning_frequencies(&self, region: &Region) -> Vec<(Position, Option<i64>)> {
+        self.uncovered(region)
+            .into_iter()
+            .map(|position| (position, position.tuning_frequency()))
+            .collect()
+    }
 
-        
-                starting_position.x += 1;
-                starting_position.y -= 1;
-                if starting_position.x < 0 || starting_position.x > max || starting_position.y < 0 || starting_position.y > max {
-                    break;
-                }
-                // println!("Sensor: [{}], Up-right, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
+    // Finds a cell between 0 and `max` in both directions no sensor can see.
+    //
+    // Turned by 45 degrees (u = x + y, v = x - y) every sensor covers a
+    // square. Take the free cell with the lowest u, and the lowest v out of
+    // those. The cells left of it, above it and diagonally at (-1, +1) or
+    // (-1, -1) are covered or off the search area. So it's on a side of the
+    // search area or one or two steps outside some sensor's square, on both
+    // a u and a v line. It's enough to check where the lines one and two
+    // steps outside every square cross each other and the sides.
+    fn distress_beacon(&self, max: i64) -> Option<Position> {
+        let mut u_lines = Vec::new();
+        let mut v_lines = Vec::new();
+        for sensor in &self.sensors {
+            let (u, v) = (sensor.position.x + sensor.position.y, sensor.position.x - sensor.position.y);
+            for outside in [sensor.radius() + 1, sensor.radius() + 2] {
+                u_lines.extend([u - outside, u + outside]);
+                v_lines.extend([v - outside, v + outside]);
             }
-            println!("Sensor: [{}], Up-right, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
-
-
-            starting_position = left_edge.clone();
-            // Scans down-right, in the left direction
-            while starting_position.x != bottom_edge.x && starting_position.y != bottom_edge.y {
-                for x in (0..=starting_position.x).rev() {
-                    let current_position = Position::new(x, starting_position.y);
-    
-                    if self.beacons.contains(&current_position.to_tuple()) {
-                        continue;
-                    }
-    
-                    if self.is_reachable_by_any_sensor(&current_position) {
-                        // should we break here?
-                        break;
-                    }
-    
-                    return current_position.clone();
-                }
+        }
 
-        
-                starting_position.x += 1;
-                starting_position.y += 1;
-                if starting_position.x < 0 || starting_position.x > max || starting_position.y < 0 || starting_position.y > max {
-                    break;
+        let mut candidates = vec![
+            Position::new(0, 0),
+            Position::new(0, max),
+            Position::new(max, 0),
+            Position::new(max, max),
+        ];
+        for u in &u_lines {
+            for v in &v_lines {
+                if (u + v) % 2 == 0 {
+                    candidates.push(Position::new((u + v) / 2, (u - v) / 2));
                 }
-                // println!("Sensor: [{}], Down-right, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
             }
-            println!("Sensor: [{}], Down-right, Starting {:?}, vs: {:?}", idx, starting_position, bottom_edge);
-
-
+        }
+        for side in [0, max] {
+            for u in &u_lines {
+                candidates.push(Position::new(side, u - side));
+                candidates.push(Position::new(u - side, side));
+            }
+            for v in &v_lines {
+                candidates.push(Position::new(side, side - v));
+                candidates.push(Position::new(v + side, side));
+            }
         }
 
-        panic!("Not found....");
+        candidates.into_iter().find(|candidate| {
+            (0..=max).contains(&candidate.x)
+                && (0..=max).contains(&candidate.y)
+                && !self.is_reachable_by_any_sensor(candidate)
+        })
     }
 
-
     fn from_file(inputfile: &str) -> Map {
         let file = match File::open(inputfile) {
             Err(why) => panic!("Couldn't open file {}", why),
@@ -251,10 +244,8 @@ impl Map {
 
         let mut map = Map::new();
     
-        for line in reader {
-            if let Ok(line) = line {
-                map.add_sensor(Sensor::parse(line.as_str()));
-            }
+        for line in reader.map_while(Result::ok) {
+            map.add_sensor(Sensor::parse(line.as_str()));
         }
     
         map
@@ -262,6 +253,15 @@ impl Map {
 }
 
 impl Sensor {
+    // how far the sensor can see
+    fn radius(&self) -> i64 {
+        self.position.manhattan(&self.closest_beacon.position)
+    }
+
+    fn covers(&self, position: &Position) -> bool {
+        self.position.manhattan(position) <= self.radius()
+    }
+
     fn parse(str_line: &str) -> Sensor {
         let regex = Regex::new(r"^Sensor at x=(?P<sensor_x>-?\d+), y=(?P<sensor_y>-?\d+): closest beacon is at x=(?P<beacon_x>-?\d+), y=(?P<beacon_y>-?\d+)$").unwrap();
         let caps = regex.captures(str_line).unwrap();
@@ -284,10 +284,41 @@ impl Sensor {
 pub fn solve() {
     let map = Map::from_file("inputs/15_input");
 
-    // println!("Part 1: {}", map.unavailable_beacon_positions(2000000));
-    println!("Part 2: {:?}", map.can_be(4000000));
+    println!("Part 1: {}", map.unavailable_beacon_positions(2000000));
+    match map.distress_beacon(4000000) {
+        Some(beacon) => match beacon.tuning_frequency() {
+            Some(frequency) => println!("Part 2: {}", frequency),
+            None => println!("Part 2: the tuning frequency is too big to work out"),
+        },
+        None => println!("Part 2: there's nowhere left for the beacon"),
+    }
+}
+
+// Part 2 the slow way, row by row through the whole search area. Also tells
+// which sensors can see the cells right next to the beacon.
+pub fn scan() {
+    let map = Map::from_file("inputs/15_input");
+    let region = Region::square(4000000);
+
+    println!("{} cells are covered", map.covered_area(&region));
+    for (position, frequency) in map.tuning_frequencies(&region) {
+        match frequency {
+            Some(frequency) => println!("{},{} has tuning frequency {}", position.x, position.y, frequency),
+            None => println!("{},{} has a tuning frequency too big to work out", position.x, position.y),
+        }
+        for (dx, dy) in [(0, -1), (-1, 0), (1, 0), (0, 1)] {
+            let neighbour = Position::new(position.x + dx, position.y + dy);
+            let sensors: Vec<String> = map
+                .sensors_covering(&neighbour)
+                .iter()
+                .map(|sensor| format!("{},{}", sensor.position.x, sensor.position.y))
+                .collect();
+            println!("  {},{} is seen by {}", neighbour.x, neighbour.y, sensors.join(" and "));
+        }
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -322,8 +353,132 @@ mod tests {
     fn base_part2() {
         let map = Map::from_file("inputs/15_base");
 
-        let pos = map.can_be(20);
+        let pos = map.distress_beacon(20).unwrap();
         assert_eq!(14, pos.x);
         assert_eq!(11, pos.y);
     }
+
+    #[test]
+    fn merged_rows() {
+        let map = Map::from_file("inputs/15_base");
+
+        assert_eq!(vec![-2..=24], map.covered(10));
+        // the gap the distress beacon is in
+        assert_eq!(vec![-3..=13, 15..=25], map.covered(11));
+        assert_eq!(Vec::<RangeInclusive<i64>>::new(), map.covered(-20));
+    }
+
+    #[test]
+    fn region_queries() {
+        let map = Map::from_file("inputs/15_base");
+        let square = Region::square(20);
+
+        assert_eq!(vec![Position::new(14, 11)], map.uncovered(&square));
+        assert_eq!(21 * 21 - 1, map.covered_area(&square));
+        assert_eq!(vec![(Position::new(14, 11), Some(56000011))], map.tuning_frequencies(&square));
+
+        // the left edge of the map is only partly covered
+        let edge = Region { x: -4..=-1, y: 9..=12 };
+        assert_eq!(
+            vec![(9, -4..=-2), (10, -4..=-3), (11, -4..=-4), (12, -4..=-3)],
+            map.gaps(&edge).collect::<Vec<_>>()
+        );
+        assert_eq!(8, map.uncovered(&edge).len());
+        assert_eq!(16 - 8, map.covered_area(&edge));
+    }
+
+    #[test]
+    fn covering_sensors() {
+        let map = Map::from_file("inputs/15_base");
+
+        let covering: Vec<(i64, i64)> = map
+            .sensors_covering(&Position::new(12, 12))
+            .iter()
+            .map(|sensor| sensor.position.to_tuple())
+            .collect();
+        assert_eq!(vec![(12, 14), (8, 7)], covering);
+        assert!(map.sensors_covering(&Position::new(14, 11)).is_empty());
+    }
+
+    #[test]
+    fn large_frequencies() {
+        let far = Position::new(3_000_000_000, 1);
+
+        assert_eq!(Some(12_000_000_000_000_001), far.tuning_frequency());
+        assert_eq!(None, Position::new(i64::MAX / 1000, 0).tuning_frequency());
+    }
+
+    #[test]
+    fn beacon_on_the_side() {
+        let mut map = Map::new();
+        map.add_sensor(Sensor::parse("Sensor at x=1, y=1: closest beacon is at x=1, y=3"));
+
+        assert_eq!(None, map.distress_beacon(2).map(|pos| pos.to_tuple()));
+        // no other sensor to cross lines with, only the corners are free
+        assert_eq!(Some((0, 3)), map.distress_beacon(3).map(|pos| pos.to_tuple()));
+    }
+
+    #[test]
+    fn beacon_two_steps_outside() {
+        let mut map = Map::new();
+        for line in [
+            "Sensor at x=7, y=0: closest beacon is at x=9, y=1",
+            "Sensor at x=1, y=4: closest beacon is at x=-1, y=5",
+            "Sensor at x=0, y=2: closest beacon is at x=-3, y=0",
+            "Sensor at x=7, y=7: closest beacon is at x=9, y=10",
+        ] {
+            map.add_sensor(Sensor::parse(line));
+        }
+
+        // two steps away from the sensors above and below it, so it's not
+        // on any line just outside their reach
+        assert_eq!(Some((5, 3)), map.distress_beacon(6).map(|pos| pos.to_tuple()));
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        // xorshift, the maps only have to be different from each other
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let mut random = |bound: i64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as i64
+        };
+
+        for _ in 0..20000 {
+            let max = 4 + random(6);
+            let mut map = Map::new();
+            for _ in 0..2 + random(5) {
+                let (x, y) = (random(max + 5) - 2, random(max + 5) - 2);
+                let (dx, dy) = (random(2 * max) - max, random(2 * max) - max);
+                map.add_sensor(Sensor {
+                    position: Position::new(x, y),
+                    closest_beacon: Beacon {
+                        position: Position::new(x + dx, y + dy),
+                    },
+                });
+            }
+
+            let free = (0..=max)
+                .flat_map(|y| (0..=max).map(move |x| Position::new(x, y)))
+                .find(|pos| !map.is_reachable_by_any_sensor(pos));
+            match map.distress_beacon(max) {
+                Some(beacon) => {
+                    assert!((0..=max).contains(&beacon.x) && (0..=max).contains(&beacon.y));
+                    assert!(!map.is_reachable_by_any_sensor(&beacon));
+                }
+                None => assert_eq!(None, free.map(|pos| pos.to_tuple()), "max {}", max),
+            }
+        }
+    }
+
+    #[test]
+    fn answers() {
+        let map = Map::from_file("inputs/15_input");
+
+        assert_eq!(5142231, map.unavailable_beacon_positions(2000000));
+        let beacon = map.distress_beacon(4000000).unwrap();
+        assert_eq!(Some(10884459367718), beacon.tuning_frequency());
+    }
 }
@@ -1,25 +1,156 @@
-use std::borrow::Borrow;
-use std::cell::RefCell;
-use std::collections::{hash_map, HashSet, VecDeque};
-use std::rc::Rc;
-use std::{collections::HashMap};
+use std::cmp::{max, min};
+use std::collections::{hash_map, HashMap, VecDeque};
 use std::{fs::File};
 use std::io::{self, BufRead};
 
 use regex::Regex;
+use serde_json::{json, Value};
 
 pub fn solve() {
     let volcano = Volcano::from_file("inputs/16_input");
 
-    println!("Starting volcano: {:?}", volcano);
-    volcano.start_with_elephant();
+    match volcano.best_plan("AA", 30) {
+        Some(plan) => println!("Part 1: {}", plan.pressure),
+        None => println!("There's no valve AA"),
+    }
+    // four minutes go into teaching the elephant
+    let team = [Agent::new("AA", 26), Agent::new("AA", 26)];
+    match volcano.best_team_plan(&team) {
+        Some(plan) => println!("Part 2: {}", plan.pressure),
+        None => println!("There's no valve AA"),
+    }
+}
+
+// Shows the plans, `dot` and `json` export the tunnels with part 1's plan on
+// them, anything else goes through part 2 minute by minute.
+pub fn show(format: Option<&str>) {
+    let volcano = Volcano::from_file("inputs/16_input");
+
+    match format {
+        Some("dot") => println!("{}", volcano.to_dot(volcano.best_plan("AA", 30).as_ref())),
+        Some("json") => println!("{}", volcano.to_json(volcano.best_plan("AA", 30).as_ref())),
+        _ => {
+            let team = [Agent::new("AA", 26), Agent::new("AA", 26)];
+            match volcano.best_team_plan(&team) {
+                Some(plan) => println!("{}", volcano.timeline(&team, &plan.plans)),
+                None => println!("There's no valve AA"),
+            }
+        }
+    }
 }
 
 
 #[derive(Debug)]
 struct Volcano {
     valves: HashMap<String, Valve>,
-    max_pressure: Rc<RefCell<i32>>,
+}
+
+// A valve getting opened, it releases pressure from the next minute on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Opening {
+    valve: String,
+    // counting from 1, same as the puzzle
+    minute: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Plan {
+    pressure: i32,
+    schedule: Vec<Opening>,
+}
+
+// Someone opening valves, starting at `start` with `minutes` to go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Agent {
+    start: String,
+    minutes: i32,
+}
+
+impl Agent {
+    fn new(start: &str, minutes: i32) -> Agent {
+        Agent {
+            start: start.to_string(),
+            minutes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TeamPlan {
+    pressure: i32,
+    // one for every agent, in the same order
+    plans: Vec<Plan>,
+}
+
+// Only the valves worth opening, along with how many minutes it takes to
+// walk between any two of them and from the start. Sets of opened valves are
+// bitmasks, bit `i` standing for `valves[i]`. The valves are sorted by name,
+// so networks with different starts agree on the bits.
+#[derive(Debug)]
+struct Network {
+    valves: Vec<(String, i32)>,
+    distances: Vec<Vec<Option<i32>>>,
+    from_start: Vec<Option<i32>>,
+}
+
+impl Network {
+    // The best plan for every set of valves that can be opened in time,
+    // keyed by the bitmask of the set. Walks through every order the valves
+    // can be opened in, giving up on a branch once there's no time left.
+    fn plans(&self, minutes: i32) -> HashMap<u64, Plan> {
+        let mut plans = HashMap::new();
+        let mut schedule = Vec::new();
+        plans.insert(0, Plan { pressure: 0, schedule: Vec::new() });
+        self.explore(None, 0, minutes, 0, 0, &mut schedule, &mut plans);
+
+        plans
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn explore(
+        &self,
+        current: Option<usize>,
+        minute: i32,
+        minutes: i32,
+        opened: u64,
+        pressure: i32,
+        schedule: &mut Vec<Opening>,
+        plans: &mut HashMap<u64, Plan>,
+    ) {
+        for (next, (name, flow_rate)) in self.valves.iter().enumerate() {
+            if opened & (1 << next) != 0 {
+                continue;
+            }
+            let distance = match current {
+                Some(current) => self.distances[current][next],
+                None => self.from_start[next],
+            };
+            // walking there and a minute to open it
+            let opened_at = match distance {
+                Some(distance) if minute + distance + 1 < minutes => minute + distance + 1,
+                _ => continue,
+            };
+
+            let opened = opened | (1 << next);
+            let pressure = pressure + flow_rate * (minutes - opened_at);
+            schedule.push(Opening {
+                valve: name.clone(),
+                minute: opened_at,
+            });
+            if plans.get(&opened).is_none_or(|plan| plan.pressure < pressure) {
+                plans.insert(opened, Plan { pressure, schedule: schedule.clone() });
+            }
+            self.explore(Some(next), opened_at, minutes, opened, pressure, schedule, plans);
+            schedule.pop();
+        }
+    }
+
+    fn best_plan(&self, minutes: i32) -> Plan {
+        self.plans(minutes)
+            .into_values()
+            .max_by_key(|plan| plan.pressure)
+            .unwrap()
+    }
 }
 
 impl Volcano {
@@ -30,208 +161,326 @@ impl Volcano {
         };
     
         let reader = io::BufReader::new(file).lines();
-
+        let regex = Regex::new(r"^Valve (?P<valve_name>[A-Z]{2}) has flow rate=(?P<flow_rate>\d+); tunnels? leads? to valves? (.*)$").unwrap();
 
         let mut valves: HashMap<String, Valve> = HashMap::new();
     
-        for line in reader {
-            if let Ok(line) = line {
-                let regex = Regex::new(r"^Valve (?P<valve_name>[A-Z]{2}) has flow rate=(?P<flow_rate>\d+); tunnels? leads? to valves? (.*)$").unwrap();
-                let caps = regex.captures(&line).unwrap();
+        for line in reader.map_while(Result::ok) {
+            let caps = regex.captures(&line).unwrap();
 
-                let valve_name = caps.name("valve_name").unwrap().as_str().to_string();
-                let flow_rate: i32 = caps.name("flow_rate").unwrap().as_str().parse().unwrap();
+            let valve_name = caps.name("valve_name").unwrap().as_str().to_string();
+            let flow_rate: i32 = caps.name("flow_rate").unwrap().as_str().parse().unwrap();
 
-                let valves_names = caps.get(3).unwrap().as_str().split(", ");
-
-                let valve = match valves.entry(valve_name.clone()) {
-                    hash_map::Entry::Occupied(o) => o.into_mut(),
-                    hash_map::Entry::Vacant(v) => v.insert(Valve::new(valve_name, flow_rate)),
-                };
-                for valve_name in valves_names {
-                    valve.connected_to.push(valve_name.to_string());
+            let valves_names = caps.get(3).unwrap().as_str().split(", ");
 
-                }
+            let valve = match valves.entry(valve_name.clone()) {
+                hash_map::Entry::Occupied(o) => o.into_mut(),
+                hash_map::Entry::Vacant(v) => v.insert(Valve::new(valve_name, flow_rate)),
+            };
+            for valve_name in valves_names {
+                valve.connected_to.push(valve_name.to_string());
             }
         }
     
-        Volcano { valves, max_pressure: Rc::new(RefCell::new(0)) }
+        Volcano { valves }
     }
 
-    fn start(&self) {
-        let opened: HashSet<&str> = HashSet::new();
-        self.simulate("AA", opened, 1, 0, vec![]);
+    // Minutes it takes to walk from `from` to every valve it can reach.
+    fn distances_from(&self, from: &str) -> HashMap<&str, i32> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        if let Some(valve) = self.valves.get(from) {
+            distances.insert(valve.name.as_str(), 0);
+            queue.push_back(valve);
+        }
 
-        println!("Max pressure: {}", *self.max_pressure.borrow_mut());
-    }
+        while let Some(valve) = queue.pop_front() {
+            let distance = distances[valve.name.as_str()];
+            for neighbour in &valve.connected_to {
+                let Some(neighbour) = self.valves.get(neighbour) else {
+                    continue;
+                };
+                if !distances.contains_key(neighbour.name.as_str()) {
+                    distances.insert(neighbour.name.as_str(), distance + 1);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
 
+        distances
+    }
 
-    fn start_with_elephant(&self) {
-        let opened: HashSet<&str> = HashSet::new();
-        self.simulate_with_elephant("AA", "AA", opened, 5, 5, 0);
+    // None if there's no such start valve.
+    fn network(&self, start: &str) -> Option<Network> {
+        self.valves.get(start)?;
+
+        let mut valves: Vec<(String, i32)> = self
+            .valves
+            .values()
+            .filter(|valve| valve.flow_rate > 0)
+            .map(|valve| (valve.name.clone(), valve.flow_rate))
+            .collect();
+        valves.sort();
+        assert!(valves.len() <= 64, "too many valves to keep track of");
+
+        let to = |distances: HashMap<&str, i32>| -> Vec<Option<i32>> {
+            valves.iter().map(|(name, _)| distances.get(name.as_str()).copied()).collect()
+        };
+        let distances = valves.iter().map(|(name, _)| to(self.distances_from(name))).collect();
+        let from_start = to(self.distances_from(start));
 
-        println!("Max pressure: {}", *self.max_pressure.borrow_mut());
+        Some(Network { valves, distances, from_start })
     }
 
+    // The most pressure one can release in `minutes` starting from `start`,
+    // along with the order to open the valves in.
+    fn best_plan(&self, start: &str, minutes: i32) -> Option<Plan> {
+        self.network(start).map(|network| network.best_plan(minutes))
+    }
 
-    fn simulate(&self, current_valve: &str, opened: HashSet<&str>, current_minute: i32, max_pressure: i32, path: Vec<(&str, i32, i32)>) {
-        let maximum_pressures = self.calculate_shortest_paths(
-            current_valve, 
-            current_minute, 
-            opened.clone()
-        );
-
-        if maximum_pressures.len() == 0 {
-            let mut volcano_pressure = self.max_pressure.borrow_mut();
-            if max_pressure > *volcano_pressure {
-                *volcano_pressure = max_pressure;
+    // Splits the valves between the agents so that together they release as
+    // much pressure as possible, every valve opened by at most one of them.
+    // None if one of them starts from a valve that doesn't exist.
+    //
+    // Everyone finishes at the same minute, so the ones with less time start
+    // later. The minutes in the plans count from when the first agents start.
+    //
+    // Every agent gets the best plan for every set of valves on its own,
+    // then the agents are added one at a time: `best[set]` is the most the
+    // agents so far can release opening exactly the valves in `set`. Only the
+    // sets the agents can actually open in time show up.
+    fn best_team_plan(&self, agents: &[Agent]) -> Option<TeamPlan> {
+        let networks = agents
+            .iter()
+            .map(|agent| self.network(&agent.start))
+            .collect::<Option<Vec<Network>>>()?;
+        let minutes = agents.iter().map(|agent| agent.minutes).max().unwrap_or(0);
+
+        let mut tables: Vec<HashMap<u64, Plan>> = Vec::new();
+        // for every agent and set, the valves that agent opens
+        let mut choices: Vec<HashMap<u64, u64>> = Vec::new();
+        let mut best: HashMap<u64, i32> = HashMap::from([(0, 0)]);
+        for (agent, network) in agents.iter().zip(&networks) {
+            let plans = network.plans(agent.minutes);
+            // the same answer every time, no matter the hash maps' order
+            let mut opened: Vec<&u64> = plans.keys().collect();
+            opened.sort();
+            let mut before: Vec<(&u64, &i32)> = best.iter().collect();
+            before.sort();
+
+            let mut next: HashMap<u64, i32> = HashMap::new();
+            let mut choice: HashMap<u64, u64> = HashMap::new();
+            for own in opened {
+                let pressure = plans[own].pressure;
+                for (others, others_pressure) in before.iter().filter(|(others, _)| *others & own == 0) {
+                    let set = own | *others;
+                    if next.get(&set).is_none_or(|best| pressure + *others_pressure > *best) {
+                        next.insert(set, pressure + *others_pressure);
+                        choice.insert(set, *own);
+                    }
+                }
             }
-            // println!("Can't go nowhere, {}, {:?}, path: {:?}", max_pressure, current_minute, path);
+
+            best = next;
+            choices.push(choice);
+            tables.push(plans);
         }
 
-        for (valve, (needed_minutes, pressure)) in &maximum_pressures {
-            let mut opened = opened.clone();
-            opened.insert(*valve);
-            if current_minute + needed_minutes < 30 {
-                let mut path = path.clone();
-                path.push((valve, *needed_minutes, *pressure));
-                self.simulate(valve, opened, current_minute + needed_minutes, max_pressure + pressure, path.clone());
+        let (mut set, pressure) = best
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(set, pressure)| (*set, *pressure))
+            .unwrap_or((0, 0));
+        let mut plans = vec![Plan { pressure: 0, schedule: Vec::new() }; agents.len()];
+        for (idx, plans_of_agent) in tables.iter().enumerate().rev() {
+            let own = choices[idx][&set];
+            let late = minutes - agents[idx].minutes;
+            let mut plan = plans_of_agent[&own].clone();
+            for opening in plan.schedule.iter_mut() {
+                opening.minute += late;
             }
+            plans[idx] = plan;
+            set &= !own;
         }
+
+        Some(TeamPlan { pressure, plans })
     }
 
-    fn simulate_with_elephant(&self, current_valve: &str, current_elephant: &str, opened: HashSet<&str>, my_minute: i32, elephants_minute: i32, max_pressure: i32) {
-        let maximum_pressures = self.calculate_shortest_paths(
-            current_valve, 
-            my_minute, 
-            opened.clone()
-        );
-        let maximum_pressures_elephant = self.calculate_shortest_paths(
-            current_elephant, 
-            elephants_minute, 
-            opened.clone()
-        );
+    // The valves sorted by name, so exports come out the same every time.
+    fn sorted_valves(&self) -> Vec<&Valve> {
+        let mut valves: Vec<&Valve> = self.valves.values().collect();
+        valves.sort_by(|a, b| a.name.cmp(&b.name));
+        valves
+    }
 
-        if maximum_pressures.len() == 0 && maximum_pressures_elephant.len() == 0 {
-            let mut volcano_pressure = self.max_pressure.borrow_mut();
-            if max_pressure > *volcano_pressure {
-                println!("Can't go nowhere, {}, my: {}, elephants: {:?}", max_pressure, my_minute, elephants_minute);
-                *volcano_pressure = max_pressure;
-            }
-        }
+    // Where in the plan every valve gets opened, counting from 1.
+    fn opening_order(plan: Option<&Plan>) -> HashMap<&str, (usize, i32)> {
+        plan.map(|plan| {
+            plan.schedule
+                .iter()
+                .enumerate()
+                .map(|(idx, opening)| (opening.valve.as_str(), (idx + 1, opening.minute)))
+                .collect()
+        })
+        .unwrap_or_default()
+    }
 
-        // we need to get the shortest for us
-        // then shortest for elephant
-        // and try all of the combinations
-        let mut xd_pressures: Vec<(&str, i32, i32, i32)> = vec![];
-        for (valve, (minutes, my_pressure)) in &maximum_pressures {
-            xd_pressures.push((*valve, *minutes, *my_pressure, my_pressure / minutes));            
+    // The tunnels as a Graphviz graph, every valve labeled with its flow
+    // rate. The valves the plan opens are filled in and numbered in order.
+    fn to_dot(&self, plan: Option<&Plan>) -> String {
+        let order = Volcano::opening_order(plan);
+        let mut lines = vec!["graph volcano {".to_string()];
+
+        for valve in self.sorted_valves() {
+            lines.push(match order.get(valve.name.as_str()) {
+                Some((position, minute)) => format!(
+                    "    {} [label=\"{}\\nrate {}\\n#{} at minute {}\", style=filled, fillcolor=orange];",
+                    valve.name, valve.name, valve.flow_rate, position, minute
+                ),
+                None => format!("    {} [label=\"{}\\nrate {}\"];", valve.name, valve.name, valve.flow_rate),
+            });
         }
-        xd_pressures.sort_by(|a, b| b.3.cmp(&a.3));
 
-        for (idx, (my_valve, my_needed_minutes, my_pressure, _)) in xd_pressures.iter().enumerate() {
-            let mut opened = opened.clone();
-            if idx >= 3 {
-                continue;
-            }
-            opened.insert(*my_valve);
-
-            for (elephant_valve, (elephant_needed_minutes, elephant_pressure)) in &maximum_pressures_elephant {
-                if opened.contains(elephant_valve) || *elephant_needed_minutes > 6 {
-                    continue;
-                }
-                let mut opened = opened.clone();
-                opened.insert(&elephant_valve);
+        // the tunnels go both ways, each is only drawn once
+        let mut tunnels: Vec<(&str, &str)> = self
+            .valves
+            .values()
+            .flat_map(|valve| {
+                valve.connected_to.iter().map(|other| {
+                    let (a, b) = (valve.name.as_str(), other.as_str());
+                    (min(a, b), max(a, b))
+                })
+            })
+            .collect();
+        tunnels.sort();
+        tunnels.dedup();
+        for (a, b) in tunnels {
+            lines.push(format!("    {} -- {};", a, b));
+        }
 
-                let mut max_pressure = max_pressure;
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
 
+    fn to_json(&self, plan: Option<&Plan>) -> Value {
+        let order = Volcano::opening_order(plan);
+        let valves: Vec<Value> = self
+            .sorted_valves()
+            .iter()
+            .map(|valve| {
+                json!({
+                    "name": valve.name,
+                    "flow_rate": valve.flow_rate,
+                    "tunnels": valve.connected_to,
+                    "opened": order.get(valve.name.as_str()).map(|(position, minute)| json!({
+                        "order": position,
+                        "minute": minute,
+                    })),
+                })
+            })
+            .collect();
+        let schedule: Vec<Value> = plan
+            .map(|plan| &plan.schedule[..])
+            .unwrap_or_default()
+            .iter()
+            .map(|opening| json!({"valve": opening.valve, "minute": opening.minute}))
+            .collect();
+
+        json!({"valves": valves, "schedule": schedule})
+    }
 
-                let mut next_my_valve = current_valve;
-                let mut next_my_minute = my_minute;
-                if my_minute + my_needed_minutes < 30 {
-                    max_pressure += my_pressure;
-                    next_my_valve = my_valve;
-                    next_my_minute += my_needed_minutes;
+    // One of the shortest ways from `from` to `to`, both included. Tunnels
+    // are tried in the order they're listed, same as the puzzle's examples.
+    fn path(&self, from: &str, to: &str) -> Option<Vec<&str>> {
+        // the valve every reached one was reached from, None for the start
+        let mut previous: HashMap<&str, Option<&str>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        let start = self.valves.get(from)?;
+        previous.insert(&start.name, None);
+        queue.push_back(start);
+
+        while let Some(valve) = queue.pop_front() {
+            if valve.name == to {
+                let mut path = vec![valve.name.as_str()];
+                while let Some(before) = previous[path.last().unwrap()] {
+                    path.push(before);
                 }
-                let mut next_elephant_valve = current_elephant;
-                let mut next_elephant_minutes = elephants_minute;
-                if elephants_minute + elephant_needed_minutes < 30 {
-                    max_pressure += elephant_pressure;
-                    next_elephant_valve = elephant_valve;
-                    next_elephant_minutes += elephant_needed_minutes;
-                }
-            
-
-                self.simulate_with_elephant(next_my_valve, next_elephant_valve, opened, next_my_minute, next_elephant_minutes, max_pressure)
+                path.reverse();
+                return Some(path);
             }
-        }
-    }
-
-    // how abouttt
-    // we know how much pressure will each valve release and 
-    // let's take every possible solution XD 
-    // basically, calculate the shortest paths at each step and try em all
-
-    fn calculate_shortest_paths(&self, current_valve: &str, starting_minute: i32, opened: HashSet<&str>) -> HashMap<&str, (i32, i32)> {
-        let current_valve = self.valves.get(current_valve).unwrap();
-
-        let mut visited: HashSet<&str> = HashSet::new();
-        let mut queue: VecDeque<&str> = VecDeque::new();
-        queue.push_back(&current_valve.name);
-
-        let max_minutes = 30;
-
-        let mut current_minute = starting_minute;
-        let mut maximum_pressures: HashMap<&str, (i32, i32)> = HashMap::new();
-
-        let mut everything_open = true;
-        for (name, valve) in &self.valves {
-            if valve.flow_rate != 0 && !opened.contains(name.as_str()) {
-                everything_open = false;
+            for neighbour in &valve.connected_to {
+                let Some(neighbour) = self.valves.get(neighbour) else {
+                    continue;
+                };
+                if !previous.contains_key(neighbour.name.as_str()) {
+                    previous.insert(&neighbour.name, Some(&valve.name));
+                    queue.push_back(neighbour);
+                }
             }
         }
-        if everything_open {
-            return maximum_pressures;
-        }
-
 
-        // this algorithm just takes the shortest path with the maximum reward
-        // it doesnt take time into dimension.
-        while queue.len() > 0 {
-            if current_minute == 30 {
-                break;
-            }
-            let mut valves_to_process = queue.len();
-
-            while valves_to_process > 0 {
-                let current_valve = self.valves.get(queue.pop_front().unwrap()).unwrap();
-
-                visited.insert(&current_valve.name);
-                
-                // let's traverse the graph and find the valve with the maximum flow rate
-                if !opened.contains(current_valve.name.as_str()) {
-                    let minutes_after_opening = max_minutes - current_minute;
-                    let current_possible_pressure = current_valve.flow_rate * minutes_after_opening;
-                    if current_possible_pressure > maximum_pressures.get(current_valve.name.as_str()).or(Some(&(0, 0))).unwrap().1 {
-                        maximum_pressures.insert(&current_valve.name, (current_minute - starting_minute + 1, current_possible_pressure));
-                    }
-                }
+        None
+    }
 
-                for neighbour in &current_valve.connected_to {
-                    if visited.contains(neighbour.as_str()) {
-                       continue; 
-                    }
-                    queue.push_back(neighbour);
+    // What everyone is doing minute by minute, worded the same as the
+    // puzzle's examples so the two can be diffed. The first agent is "You",
+    // the others are elephants. Same as in `best_team_plan` the ones with less
+    // time start later, so everyone is done after the last minute.
+    fn timeline(&self, agents: &[Agent], plans: &[Plan]) -> String {
+        let minutes = agents.iter().map(|agent| agent.minutes).max().unwrap_or(0);
+        // the name and how it goes with moving and opening
+        let names: Vec<(String, &str, &str)> = (0..agents.len())
+            .map(|idx| match (idx, agents.len()) {
+                (0, _) => ("You".to_string(), "move", "open"),
+                (1, 2) => ("The elephant".to_string(), "moves", "opens"),
+                (idx, _) => (format!("Elephant {}", idx), "moves", "opens"),
+            })
+            .collect();
+
+        // what every agent does in every minute, minute 1 at index 0
+        let mut actions = vec![vec![None; minutes as usize]; agents.len()];
+        for (idx, (agent, plan)) in agents.iter().zip(plans).enumerate() {
+            let (name, moves, opens) = &names[idx];
+            let mut position = agent.start.as_str();
+            let mut minute = (minutes - agent.minutes) as usize;
+            for opening in &plan.schedule {
+                let path = self.path(position, &opening.valve).unwrap_or_default();
+                for step in path.iter().skip(1) {
+                    actions[idx][minute] = Some(format!("{} {} to valve {}.", name, moves, step));
+                    minute += 1;
                 }
-
-                valves_to_process -= 1;
+                minute = opening.minute as usize - 1;
+                actions[idx][minute] = Some(format!("{} {} valve {}.", name, opens, opening.valve));
+                minute += 1;
+                position = &opening.valve;
             }
+        }
 
-            current_minute += 1;
+        let mut openings: Vec<&Opening> = plans.iter().flat_map(|plan| &plan.schedule).collect();
+        openings.sort_by_key(|opening| (opening.minute, opening.valve.clone()));
+
+        let mut blocks = Vec::new();
+        for minute in 1..=minutes {
+            let mut open: Vec<&str> = openings
+                .iter()
+                .filter(|opening| opening.minute < minute)
+                .map(|opening| opening.valve.as_str())
+                .collect();
+            open.sort();
+            let releasing: i32 = open.iter().map(|name| self.valves[*name].flow_rate).sum();
+
+            let mut lines = vec![format!("== Minute {} ==", minute)];
+            lines.push(match open.as_slice() {
+                [] => "No valves are open.".to_string(),
+                [valve] => format!("Valve {} is open, releasing {} pressure.", valve, releasing),
+                [first, second] => format!("Valves {} and {} are open, releasing {} pressure.", first, second, releasing),
+                [rest @ .., last] => format!("Valves {}, and {} are open, releasing {} pressure.", rest.join(", "), last, releasing),
+            });
+            lines.extend(actions.iter().filter_map(|agent| agent[minute as usize - 1].clone()));
+            blocks.push(lines.join("\n"));
         }
 
-        // println!("Maximum pressure: {:?}", maximum_pressures);
-        maximum_pressures
+        blocks.join("\n\n")
     }
 
 }
@@ -256,6 +505,7 @@ impl Valve {
 
 
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -263,17 +513,288 @@ mod tests {
     fn sanity() {
         let volcano = Volcano::from_file("inputs/16_base");
 
-        println!("Starting volcano: {:?}", volcano);
-        volcano.start();
-        assert!(volcano.valves.len() > 0)
+        assert_eq!(10, volcano.valves.len());
+        assert_eq!(vec!["DD", "II", "BB"], volcano.valves["AA"].connected_to);
+        assert_eq!(Some(&2), volcano.distances_from("AA").get("JJ"));
+    }
+
+    #[test]
+    fn compressed() {
+        let network = Volcano::from_file("inputs/16_base").network("AA").unwrap();
+
+        let names: Vec<&str> = network.valves.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["BB", "CC", "DD", "EE", "HH", "JJ"], names);
+        assert_eq!(vec![Some(1), Some(2), Some(1), Some(2), Some(5), Some(2)], network.from_start);
+        // HH to JJ goes all the way through AA
+        assert_eq!(Some(7), network.distances[4][5]);
+    }
+
+    #[test]
+    fn base() {
+        let volcano = Volcano::from_file("inputs/16_base");
+        let plan = volcano.best_plan("AA", 30).unwrap();
+
+        assert_eq!(1651, plan.pressure);
+        assert_eq!(vec![("DD", 2), ("BB", 5), ("JJ", 9), ("HH", 17), ("EE", 21), ("CC", 24)], schedule(&plan));
+    }
+
+    #[test]
+    fn other_starts_and_limits() {
+        let volcano = Volcano::from_file("inputs/16_base");
+
+        assert_eq!(Some(Plan { pressure: 0, schedule: vec![] }), volcano.best_plan("AA", 2));
+        // only enough time to open DD, a minute in, a minute to open it
+        assert_eq!(20, volcano.best_plan("AA", 3).unwrap().pressure);
+        let from_hh = volcano.best_plan("HH", 30).unwrap();
+        assert_eq!(Some("HH"), from_hh.schedule.first().map(|o| o.valve.as_str()));
+        assert_eq!(Some(1), from_hh.schedule.first().map(|o| o.minute));
+        assert_eq!(None, volcano.best_plan("ZZ", 30));
+    }
+
+    fn schedule(plan: &Plan) -> Vec<(&str, i32)> {
+        plan.schedule.iter().map(|o| (o.valve.as_str(), o.minute)).collect()
+    }
+
+    #[test]
+    fn base_part2() {
+        let volcano = Volcano::from_file("inputs/16_base");
+        let team = volcano.best_team_plan(&[Agent::new("AA", 26), Agent::new("AA", 26)]).unwrap();
+
+        assert_eq!(1707, team.pressure);
+        assert_eq!(team.pressure, team.plans.iter().map(|plan| plan.pressure).sum::<i32>());
+        let mut schedules: Vec<Vec<(&str, i32)>> = team.plans.iter().map(schedule).collect();
+        schedules.sort();
+        assert_eq!(
+            vec![
+                vec![("DD", 2), ("HH", 7), ("EE", 11)],
+                vec![("JJ", 3), ("BB", 7), ("CC", 9)],
+            ],
+            schedules
+        );
+    }
+
+    #[test]
+    fn any_number_of_agents() {
+        let volcano = Volcano::from_file("inputs/16_base");
+
+        assert_eq!(Some(TeamPlan { pressure: 0, plans: vec![] }), volcano.best_team_plan(&[]));
+        let alone = volcano.best_team_plan(&[Agent::new("AA", 30)]).unwrap();
+        assert_eq!(volcano.best_plan("AA", 30), alone.plans.first().cloned());
+
+        let three = [Agent::new("AA", 26), Agent::new("AA", 26), Agent::new("AA", 26)];
+        let team = volcano.best_team_plan(&three).unwrap();
+        assert!(team.pressure >= 1707);
+        // nobody opens a valve someone else already did
+        let mut opened: Vec<&str> = team.plans.iter().flat_map(|plan| plan.schedule.iter().map(|o| o.valve.as_str())).collect();
+        let count = opened.len();
+        opened.sort();
+        opened.dedup();
+        assert_eq!(count, opened.len());
+    }
+
+    #[test]
+    fn own_starts_and_budgets() {
+        let volcano = Volcano::from_file("inputs/16_base");
+
+        // standing right next to it, HH only needs the one minute, there's no
+        // way to get there from AA in time. With less time it starts three
+        // minutes later, everyone's done after minute 5.
+        let team = volcano.best_team_plan(&[Agent::new("AA", 5), Agent::new("HH", 2)]).unwrap();
+        assert_eq!(vec![("HH", 4)], schedule(&team.plans[1]));
+        assert_eq!(22, team.plans[1].pressure);
+        assert!(team.plans[0].schedule.iter().all(|o| o.valve != "HH"));
+        // every valve releases from the minute after it's opened up to the end
+        let released: i32 = team
+            .plans
+            .iter()
+            .flat_map(|plan| &plan.schedule)
+            .map(|o| volcano.valves[&o.valve].flow_rate * (5 - o.minute))
+            .sum();
+        assert_eq!(team.pressure, released);
+        assert_eq!(85, team.pressure);
+        // nothing left to do in no time at all
+        let idle = volcano.best_team_plan(&[Agent::new("JJ", 30), Agent::new("DD", 0)]).unwrap();
+        assert!(idle.plans[1].schedule.is_empty());
+        assert_eq!(None, volcano.best_team_plan(&[Agent::new("AA", 26), Agent::new("ZZ", 26)]));
+    }
+
+    #[test]
+    fn many_valves() {
+        // a tunnel of 41 valves, way too many sets to keep a table of them
+        let name = |idx: usize| format!("{}{}", (b'A' + (idx / 26) as u8) as char, (b'A' + (idx % 26) as u8) as char);
+        let mut valves = HashMap::new();
+        for idx in 0..41 {
+            let mut valve = Valve::new(name(idx), idx as i32);
+            valve.connected_to.extend((idx.max(1) - 1..=idx + 1).filter(|other| *other != idx && *other < 41).map(name));
+            valves.insert(name(idx), valve);
+        }
+        let volcano = Volcano { valves };
+
+        let team = volcano.best_team_plan(&[Agent::new("AA", 6), Agent::new("AA", 6)]).unwrap();
+        // AB and AC by one of them, AD by the other
+        assert_eq!(14, team.pressure);
+    }
+
+    #[test]
+    fn answers() {
+        let volcano = Volcano::from_file("inputs/16_input");
+
+        assert_eq!(1673, volcano.best_plan("AA", 30).unwrap().pressure);
+        let team = [Agent::new("AA", 26), Agent::new("AA", 26)];
+        assert_eq!(2343, volcano.best_team_plan(&team).unwrap().pressure);
     }
 
     #[test]
-    fn sanity_part2() {
+    fn timeline() {
         let volcano = Volcano::from_file("inputs/16_base");
+        let me = Agent::new("AA", 30);
+        let plan = volcano.best_plan("AA", 30).unwrap();
+        let timeline = volcano.timeline(&[me], &[plan]);
+
+        assert!(timeline.starts_with(
+            "== Minute 1 ==
+No valves are open.
+You move to valve DD.
+
+== Minute 2 ==
+No valves are open.
+You open valve DD.
+
+== Minute 3 ==
+Valve DD is open, releasing 20 pressure.
+You move to valve CC.
+
+== Minute 4 ==
+Valve DD is open, releasing 20 pressure.
+You move to valve BB.
+
+== Minute 5 ==
+Valve DD is open, releasing 20 pressure.
+You open valve BB.
+
+== Minute 6 ==
+Valves BB and DD are open, releasing 33 pressure.
+You move to valve AA.
+"
+        ));
+        assert!(timeline.contains(
+            "== Minute 24 ==
+Valves BB, DD, EE, HH, and JJ are open, releasing 79 pressure.
+You open valve CC.
+
+== Minute 25 ==
+Valves BB, CC, DD, EE, HH, and JJ are open, releasing 81 pressure.
+
+== Minute 26 =="
+        ));
+        assert!(timeline.ends_with("== Minute 30 ==\nValves BB, CC, DD, EE, HH, and JJ are open, releasing 81 pressure."));
+        let released: i32 = timeline
+            .lines()
+            .filter_map(|line| line.split("releasing ").nth(1))
+            .map(|rest| rest.trim_end_matches(" pressure.").parse::<i32>().unwrap())
+            .sum();
+        assert_eq!(1651, released);
+    }
+
+    #[test]
+    fn timeline_with_elephant() {
+        let volcano = Volcano::from_file("inputs/16_base");
+        let opening = |valve: &str, minute| Opening {
+            valve: valve.to_string(),
+            minute,
+        };
+        let plans = [
+            Plan { pressure: 0, schedule: vec![opening("JJ", 3), opening("BB", 7), opening("CC", 9)] },
+            Plan { pressure: 0, schedule: vec![opening("DD", 2), opening("HH", 7), opening("EE", 11)] },
+        ];
+        let team = [Agent::new("AA", 26), Agent::new("AA", 26)];
+
+        assert!(volcano.timeline(&team, &plans).starts_with(
+            "== Minute 1 ==
+No valves are open.
+You move to valve II.
+The elephant moves to valve DD.
+
+== Minute 2 ==
+No valves are open.
+You move to valve JJ.
+The elephant opens valve DD.
+
+== Minute 3 ==
+Valve DD is open, releasing 20 pressure.
+You open valve JJ.
+The elephant moves to valve EE.
+"
+        ));
+    }
 
-        println!("Starting volcano: {:?}", volcano);
-        volcano.start_with_elephant();
-        assert!(volcano.valves.len() > 0)
+    #[test]
+    fn timeline_with_less_time() {
+        let volcano = Volcano::from_file("inputs/16_base");
+        let team = [Agent::new("AA", 5), Agent::new("AA", 3)];
+        let plan = volcano.best_team_plan(&team).unwrap();
+        let timeline = volcano.timeline(&team, &plan.plans);
+
+        // the elephant only gets going after two minutes
+        assert!(timeline.starts_with(
+            "== Minute 1 ==
+No valves are open.
+You move to valve DD.
+
+== Minute 2 ==
+No valves are open.
+You open valve DD.
+
+== Minute 3 ==
+Valve DD is open, releasing 20 pressure.
+You move to valve EE.
+The elephant moves to valve BB.
+
+== Minute 4 ==
+Valve DD is open, releasing 20 pressure.
+You open valve EE.
+The elephant opens valve BB.
+"
+        ));
+        let released: i32 = timeline
+            .lines()
+            .filter_map(|line| line.split("releasing ").nth(1))
+            .map(|rest| rest.trim_end_matches(" pressure.").parse::<i32>().unwrap())
+            .sum();
+        assert_eq!(plan.pressure, released);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn paths() {
+        let volcano = Volcano::from_file("inputs/16_base");
+
+        // CC is listed before AA, so that's the way to go
+        assert_eq!(Some(vec!["DD", "CC", "BB"]), volcano.path("DD", "BB"));
+        assert_eq!(Some(vec!["AA"]), volcano.path("AA", "AA"));
+        assert_eq!(None, volcano.path("AA", "ZZ"));
+    }
+
+    #[test]
+    fn exports() {
+        let volcano = Volcano::from_file("inputs/16_base");
+        let plan = volcano.best_plan("AA", 30).unwrap();
+
+        let dot = volcano.to_dot(Some(&plan));
+        assert!(dot.starts_with("graph volcano {\n    AA [label=\"AA\\nrate 0\"];\n"));
+        assert!(dot.contains("    DD [label=\"DD\\nrate 20\\n#1 at minute 2\", style=filled, fillcolor=orange];"));
+        assert!(dot.contains("    AA -- BB;\n    AA -- DD;\n    AA -- II;\n"));
+        assert_eq!(1, dot.matches("CC -- DD;").count());
+        assert!(dot.ends_with("}"));
+        assert!(!volcano.to_dot(None).contains("filled"));
+
+        let json = volcano.to_json(Some(&plan));
+        assert_eq!(10, json["valves"].as_array().unwrap().len());
+        assert_eq!(
+            json!({"name": "BB", "flow_rate": 13, "tunnels": ["CC", "AA"], "opened": {"order": 2, "minute": 5}}),
+            json["valves"][1]
+        );
+        assert_eq!(Value::Null, json["valves"][0]["opened"]);
+        assert_eq!(json!({"valve": "CC", "minute": 24}), json["schedule"][5]);
+        assert_eq!(json!([]), volcano.to_json(None)["schedule"]);
+    }
+}
@@ -102,8 +102,7 @@ impl Rock {
             let next_x = x + delta_x;
             let next_y = y + delta_y;
             if next_y < 0
-                || next_x < 0
-                || next_x >= 7
+                || !(0..7).contains(&next_x)
                 || chamber.pebbles.contains(&(next_x, next_y))
             {
                 can_move = false;
@@ -189,22 +188,19 @@ impl Chamber {
 
     fn drop_rock(&mut self, mut rock: Rock) {
         // println!("The rock begins falling");
-        // self.print_with_rock(&rock);
 
         let mut xd = 0;
         loop {
             let jet = self.pattern.get(self.current_pattern).unwrap();
             self.current_pattern = (self.current_pattern + 1) % self.pattern.len();
 
-            let pushed = rock.advance(*jet, self);
+            rock.advance(*jet, self);
             // println!("Jet of gas pushes rock {:?}, but {}", *jet, pushed);
-            // self.print_with_rock(&rock);
             if !rock.advance(Direction::Down, self) {
                 // println!("Can't go down");
                 break;
             }
             // println!("Rock falls 1 unit");
-            // self.print_with_rock(&rock);
             xd += 1;
         }
         if xd > 40 { 
@@ -212,7 +208,7 @@ impl Chamber {
         }
 
         for pebble in &rock.pebbles {
-            self.pebbles.insert(pebble.clone());
+            self.pebbles.insert(*pebble);
             // let mut new_floor = true;
             // for x in 0..7  {
             //     if !self.pebbles.contains(&(x, pebble.1)) {
@@ -228,47 +224,11 @@ impl Chamber {
 
         }
 
-        for (x, y) in rock.pebbles {
+        for (_, y) in rock.pebbles {
             if y >= self.highest_y {
                 self.highest_y = y + 1;
             }
         }
-
-        // self.print();
-    }
-
-    fn print(&self) {
-        for y in (0..=self.highest_y + 5).rev() {
-            print!("|");
-            for x in 0..7 {
-                if self.pebbles.contains(&(x, y)) {
-                    print!("#");
-                } else {
-                    print!(".");
-                }
-            }
-            println!("|");
-        }
-        println!("+-------+");
-        println!();
-    }
-
-    fn print_with_rock(&self, rock: &Rock) {
-        for y in (0..=self.highest_y + 5).rev() {
-            print!("|");
-            for x in 0..7 {
-                if rock.pebbles.contains(&(x, y)) {
-                    print!("@")
-                } else if self.pebbles.contains(&(x, y)) {
-                    print!("#");
-                } else {
-                    print!(".");
-                }
-            }
-            println!("|");
-        }
-        println!("+-------+");
-        println!();
     }
 }
 
@@ -284,10 +244,10 @@ pub fn solve() {
         }
     }
 
-    // chamber.print();
     println!("Solution for part1: {}", chamber.highest_y);
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -300,7 +260,6 @@ mod tests {
             chamber.drop_rock(chamber.spawn_rock((i % 5).try_into().unwrap()));
         }
 
-        // chamber.print();
         assert_eq!(3068, chamber.highest_y);
     }
 }
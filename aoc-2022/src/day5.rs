@@ -1,22 +1,20 @@
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::fmt;
+use std::fs;
 use regex::Regex;
-use std::collections::{VecDeque};
 
-struct Move {
-    count: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    count: usize,
     from: usize,
     to: usize
 }
 
-
-
 impl Move {
     fn parse(line: &str) -> Move {
         let re = Regex::new(r"move (?P<count>\d+) from (?P<from>\d+) to (?P<to>\d+)").unwrap();
         let cap = re.captures(line).unwrap();
 
-        return Move {
+        Move {
             count: cap.name("count").unwrap().as_str().parse().unwrap(),
             from: cap.name("from").unwrap().as_str().parse().unwrap(),
             to: cap.name("to").unwrap().as_str().parse().unwrap(),
@@ -24,115 +22,361 @@ impl Move {
     }
 }
 
-fn parse(reader: io::Lines<io::BufReader<File>>) -> (Vec<VecDeque<char>>, Vec<Move>) {
-    let mut moves = Vec::new();
-    let mut crates: Vec<VecDeque<char>> = vec![VecDeque::new()];
-    
-    let mut parsing_moves = false;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crane {
+    // moves crates one at a time, so they end up reversed
+    CrateMover9000,
+    // moves all of the crates at once, keeping their order
+    CrateMover9001,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DrawingError {
+    MissingLabelRow,
+    InvalidLabel { column: usize, label: String },
+    UnterminatedCrate { row: usize, column: usize },
+    UnalignedCrate { row: usize, column: usize },
+    FloatingCrate { row: usize, column: usize },
+}
 
-    for line in reader {
-        if let Ok(entry) = line {
-            if entry == "" {
-                parsing_moves = true;
-                continue;
+impl fmt::Display for DrawingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrawingError::MissingLabelRow => write!(f, "drawing has no stack label row"),
+            DrawingError::InvalidLabel { column, label } => {
+                write!(f, "invalid stack label '{}' at column {}", label, column)
             }
+            DrawingError::UnterminatedCrate { row, column } => {
+                write!(f, "crate at row {}, column {} is missing its ']'", row, column)
+            }
+            DrawingError::UnalignedCrate { row, column } => {
+                write!(f, "crate at row {}, column {} is not above any stack label", row, column)
+            }
+            DrawingError::FloatingCrate { row, column } => {
+                write!(f, "crate at row {}, column {} has nothing underneath", row, column)
+            }
+        }
+    }
+}
 
-            if parsing_moves {
-                println!("parsing moves {}", entry);
-                moves.push(Move::parse(&entry))
-            } else {
-                let mut start_crate = false;
-                let mut crate_idx = 0;
-
-                for (i, token) in entry.chars().enumerate() {
-                    if (i + 1) % 4 == 0 {
-                        crate_idx += 1;
-                    }
-
-                    if start_crate && token != ']' {
-                        println!("token: {}", token);
-                        while crate_idx >= crates.len() {
-                            crates.push(VecDeque::new());
-                            println!("new crate! {}", crate_idx);
-                        }
-                        match crates.get_mut(crate_idx) {
-                            Some(c) => {
-                                c.push_back(token)
-                            },
-                            None => {
-                                panic!("shouldn't happen")
-                            }
-                        }
-                    } else if token == '[' {
-                        start_crate = true;
-                    } else if token == ']' {
-                        start_crate = false;
-                    }
-                }
+// A span of characters in a line of the drawing, `end` is exclusive.
+struct Token<'a> {
+    start: usize,
+    end: usize,
+    text: &'a str,
+}
+
+fn label_tokens(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (i, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                tokens.push(Token { start: s, end: i, text: &line[s..i] });
+                start = None;
             }
+            _ => {}
         }
-    };
+    }
 
-    return (crates, moves)
+    tokens
 }
 
+fn crate_tokens(line: &str, row: usize) -> Result<Vec<Token<'_>>, DrawingError> {
+    let mut tokens = Vec::new();
+    let mut start: Option<usize> = None;
 
-pub fn part1() {
-    let file = match File::open("inputs/5_input") {
-        Err(why) => panic!("Couldn't open file {}", why),
-        Ok(file) => file
-    };
+    for (i, c) in line.char_indices() {
+        match (start, c) {
+            (None, '[') => start = Some(i),
+            (Some(s), ']') => {
+                tokens.push(Token { start: s, end: i + 1, text: &line[s + 1..i] });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(s) = start {
+        return Err(DrawingError::UnterminatedCrate { row, column: s });
+    }
+
+    Ok(tokens)
+}
+
+// Stacks of crates as drawn in the puzzle, e.g.
+//     [D]
+// [N] [C]
+// [Z] [M] [P]
+//  1   2   3
+//
+// Crates can have labels wider than a single letter (`[AB]`) and there can be
+// any number of stacks, the label row is what tells us where each stack is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stacks {
+    labels: Vec<String>,
+    // bottom crate first, top crate last
+    stacks: Vec<Vec<String>>,
+}
+
+impl Stacks {
+    pub fn parse(drawing: &str) -> Result<Stacks, DrawingError> {
+        let mut lines: Vec<&str> = drawing
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+
+        let label_row = lines.pop().ok_or(DrawingError::MissingLabelRow)?;
+        let labels = label_tokens(label_row);
+        if labels.is_empty() {
+            return Err(DrawingError::MissingLabelRow);
+        }
+        for label in &labels {
+            if label.text.contains('[') || label.text.contains(']') {
+                return Err(DrawingError::InvalidLabel {
+                    column: label.start,
+                    label: label.text.to_string(),
+                });
+            }
+        }
+
+        let mut stacks: Vec<Vec<String>> = vec![Vec::new(); labels.len()];
+
+        // reading from the bottom, so every crate lands on top of the previous one
+        for (row, line) in lines.iter().enumerate().rev() {
+            for token in crate_tokens(line, row)? {
+                let stack_idx = labels
+                    .iter()
+                    .position(|l| l.start < token.end && token.start < l.end)
+                    .ok_or(DrawingError::UnalignedCrate { row, column: token.start })?;
+
+                let stack = &mut stacks[stack_idx];
+                // every row below this one should have had a crate in this stack
+                if stack.len() != lines.len() - 1 - row {
+                    return Err(DrawingError::FloatingCrate { row, column: token.start });
+                }
+                stack.push(token.text.to_string());
+            }
+        }
 
-    let reader = io::BufReader::new(file).lines();
+        Ok(Stacks {
+            labels: labels.iter().map(|l| l.text.to_string()).collect(),
+            stacks,
+        })
+    }
 
+    pub fn render(&self) -> String {
+        let crate_width = self
+            .stacks
+            .iter()
+            .flatten()
+            .map(|c| c.chars().count() + 2)
+            .max()
+            .unwrap_or(3);
+        let label_width = self.labels.iter().map(|l| l.chars().count()).max().unwrap_or(1);
+        let width = crate_width.max(label_width);
+        let height = self.stacks.iter().map(|s| s.len()).max().unwrap_or(0);
 
-    let (mut crates, moves) = parse(reader);
+        let mut lines = Vec::new();
+        for level in (0..height).rev() {
+            let row: Vec<String> = self
+                .stacks
+                .iter()
+                .map(|s| match s.get(level) {
+                    Some(c) => center(&format!("[{}]", c), width),
+                    None => " ".repeat(width),
+                })
+                .collect();
+            lines.push(row.join(" ").trim_end().to_string());
+        }
 
-    for m in moves {
-        for _ in 0..m.count {
-            let from = crates.get_mut(m.from - 1).unwrap();
-            let token = from.pop_front().unwrap();
-            let to = crates.get_mut(m.to - 1).unwrap();
+        let labels: Vec<String> = self.labels.iter().map(|l| center(l, width)).collect();
+        lines.push(labels.join(" ").trim_end().to_string());
+
+        lines.join("\n")
+    }
 
-            to.push_front(token);
+    pub fn apply(&mut self, m: &Move, crane: Crane) {
+        let from = self.stacks.get_mut(m.from - 1).unwrap();
+        let mut moved = from.split_off(from.len() - m.count);
+        if crane == Crane::CrateMover9000 {
+            moved.reverse();
         }
+
+        self.stacks.get_mut(m.to - 1).unwrap().extend(moved);
     }
 
-    for mut c in crates {
-        let token = c.pop_front().unwrap();
-        print!("{}", token);
+    pub fn tops(&self) -> String {
+        self.stacks
+            .iter()
+            .filter_map(|s| s.last())
+            .map(|c| c.as_str())
+            .collect()
     }
 }
 
+impl fmt::Display for Stacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+fn center(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    let left = (width - len) / 2;
+
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(width - len - left))
+}
+
+fn parse(input: &str) -> (Stacks, Vec<Move>) {
+    let (drawing, moves) = input.split_once("\n\n").unwrap_or((input, ""));
+
+    let stacks = match Stacks::parse(drawing) {
+        Err(why) => panic!("Couldn't parse the drawing: {}", why),
+        Ok(stacks) => stacks
+    };
+    let moves = moves
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(Move::parse)
+        .collect();
+
+    (stacks, moves)
+}
+
+fn rearrange(input: &str, crane: Crane) -> Stacks {
+    let (mut stacks, moves) = parse(input);
+    for m in &moves {
+        stacks.apply(m, crane);
+    }
+
+    stacks
+}
+
+pub fn part1() {
+    let input = match fs::read_to_string("inputs/5_input") {
+        Err(why) => panic!("Couldn't open file {}", why),
+        Ok(input) => input
+    };
+
+    println!("{}", rearrange(&input, Crane::CrateMover9000).tops());
+}
 
 pub fn part2() {
-    let file = match File::open("inputs/5_input") {
+    let input = match fs::read_to_string("inputs/5_input") {
         Err(why) => panic!("Couldn't open file {}", why),
-        Ok(file) => file
+        Ok(input) => input
     };
 
-    let reader = io::BufReader::new(file).lines();
+    println!("{}", rearrange(&input, Crane::CrateMover9001).tops());
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let (mut crates, moves) = parse(reader);
+    const EXAMPLE: &str = "    [D]
+[N] [C]
+[Z] [M] [P]
+ 1   2   3
 
-    for m in moves {
-        let mut tokens = VecDeque::new();
-        for _ in 0..m.count {
-            let from = crates.get_mut(m.from - 1).unwrap();
-            let token = from.pop_front().unwrap();
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2
+";
 
-            tokens.push_front(token);
-        }
+    #[test]
+    fn example_part1() {
+        let stacks = rearrange(EXAMPLE, Crane::CrateMover9000);
+        assert_eq!("CMZ", stacks.tops());
+        assert_eq!(
+            "        [Z]
+        [N]
+        [D]
+[C] [M] [P]
+ 1   2   3",
+            stacks.render()
+        );
+    }
 
-        let to = crates.get_mut(m.to - 1).unwrap();
-        for token in tokens {
-            to.push_front(token);
-        }
+    #[test]
+    fn example_part2() {
+        let stacks = rearrange(EXAMPLE, Crane::CrateMover9001);
+        assert_eq!("MCD", stacks.tops());
+        assert_eq!(
+            "        [D]
+        [N]
+        [Z]
+[M] [C] [P]
+ 1   2   3",
+            stacks.render()
+        );
     }
 
-    for mut c in crates {
-        let token = c.pop_front().unwrap();
-        print!("{}", token);
+    #[test]
+    fn round_trip() {
+        let drawing = "    [D]
+[N] [C]
+[Z] [M] [P]
+ 1   2   3";
+        let stacks = Stacks::parse(drawing).unwrap();
+        assert_eq!(drawing, stacks.render());
+        assert_eq!(stacks, Stacks::parse(&stacks.render()).unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn more_than_nine_stacks() {
+        let drawing = "                                        [K]
+[A] [B] [C] [D] [E] [F] [G] [H] [I] [J] [L]
+ 1   2   3   4   5   6   7   8   9  10  11";
+        let mut stacks = Stacks::parse(drawing).unwrap();
+        assert_eq!("ABCDEFGHIJK", stacks.tops());
+
+        stacks.apply(&Move { count: 1, from: 11, to: 1 }, Crane::CrateMover9000);
+        assert_eq!("KBCDEFGHIJL", stacks.tops());
+        assert_eq!(
+            "[K]
+[A] [B] [C] [D] [E] [F] [G] [H] [I] [J] [L]
+ 1   2   3   4   5   6   7   8   9  10  11",
+            stacks.render()
+        );
+    }
+
+    #[test]
+    fn wide_crates() {
+        let drawing = "      [XYZ]
+[AB]  [C]
+ 1     2    3";
+        let mut stacks = Stacks::parse(drawing).unwrap();
+        assert_eq!("ABXYZ", stacks.tops());
+
+        stacks.apply(&Move { count: 2, from: 2, to: 3 }, Crane::CrateMover9001);
+        assert_eq!(
+            "            [XYZ]
+[AB]         [C]
+  1     2     3",
+            stacks.render()
+        );
+        assert_eq!(stacks, Stacks::parse(&stacks.render()).unwrap());
+    }
+
+    #[test]
+    fn invalid_drawings() {
+        assert_eq!(Err(DrawingError::MissingLabelRow), Stacks::parse(""));
+        assert_eq!(
+            Err(DrawingError::UnterminatedCrate { row: 0, column: 0 }),
+            Stacks::parse("[A\n 1")
+        );
+        assert_eq!(
+            Err(DrawingError::UnalignedCrate { row: 0, column: 4 }),
+            Stacks::parse("[A] [B]\n 1")
+        );
+        assert_eq!(
+            Err(DrawingError::FloatingCrate { row: 0, column: 0 }),
+            Stacks::parse("[A]\n    [B]\n 1   2")
+        );
+    }
+}
@@ -1,48 +1,177 @@
-use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io::{self, Read};
 
 
 pub const INPUT_SIGNAL: &str = "pqffvllhrhthvhshhpnhpnpqpvpvrpvpwvwjjdssmcsmccjvjmjjwnjwjwhjwwwzswwhvhwwlvvlbvbtbzbfbzbtbqbgbpbggwzggvjjdpdffbmffntncchtccbcffcjfjnjfnntssvtstzssmnnhrhlhbbwfwjfwjfwwbhhfhmmpsssbnssssfzzfpffdrdpdqqvnncjjgrjjmhhpqqcjqcjjzdzzpvvprrlglrrcmcqqtltdltddswsrrzzwgzzgssczcmzzmgmwgmggwwzttpccmcsmmvfvnvppzlzvzllgclggpfggfnfrfvrvwwvhwwvgwwrbbgfglflblzblzbznzhzffplffnrrcqqsgsvshvhlldhhvnhhmdddnssdvdwdwccggmddsmswwtctdtqqjsshhjzzdpdmpdmppjtjwjswjsjjjsdjjtrtbrbjjwwvnvppqphqhwhcwhwbbpgbbnhbnhhswwdswwlcczdztzbbbnwwtmmpvvgjjqgqdqzzdjdpjjnnffhccscvvchhbmbcbffpdpggvdvttpvpqqhggdtdhtdhhmghmgggzwgwrgwggwlggvpggcfcttzmtmgmvgmmpqmqlmqllsqqjbjwjsszczlzrzgrzzhshlhjjwttwnntbtjtjpplccqrqhrhssbmbttrddfvfwwjcwcvwcwwvpvggqwgwjgwgccvqqcmqqtqnqpnqnffdqfqhhqnhnmhmvhmhwwfrwrggnmmmcnmmgsszmzlmzmddcwwthtssgjsgjjgpgnppdqqcgqggzjgjngnrnggvffgddvtvctcftcftfnnnnhssbgsgwwthtqtltftqtnqttsrtrggwcgwcwmwgmgvmmzrmzrzjzmmcclmmtjmjhmmlhlwlppnpccbbrlrqrcrjrdrlrnngmnmvmcmzczztbblglccvzvppzspsddrzzlsllfzfsspnpdnpnvvvgmmpccmpcpgcpcwcddtmddgwgngqqcpqqlhqqczqqbvqqgdqgqmgmlmmvrrgfgzffbccldcdmmcmcgcngnghngngdngdndcncbbpqbbphbphpccpcwwjswwfttbqbsszccrbbdndsdrdqrqjrjjbmbtbdbbgbvgvcggwdwcccttqccnffjpjqqzpzlzvlljhhschhzlhhfhcfhchvcvtvtgvgzvzrvrdrgrwrjwjljhljlssszsqzsswhhmlhlrhlhzzgghjhzjjcllwrwtrrbdbrbnrnprnrffjvvphhvbbqbbscstsmslmlvmvrmmvvngnlnzzwqzzjqqsqbqrrtmrtmmfgmgrgjjtmjmrrddmrrqmrrjmjqmqnqmmcmlmfmffcgcclplffzvzwvzzjtztftqftqffjjpwjjbpjjggzdgzgwzzfrfvvhfvvwcvvbccfcvffpcpgpbbqhbbhmmzfmfvvnjvnjjhzhqqmffndndmmzhmmqnnlglvvjhjddvggqwgwdgwdggqbqgqrqlrrtptsswlssqwssbdsdrsddjsszjszjjpnjnvnjvnnmznmnddccpwwhshzzcfcqcwcddsjjmnjmjljwwgmglllqlhhctcvvqrvrrhfrrbcrrfbrfbrfrqffbwbqwbbjggsjjjnqqrqsqhhwnhnshnhhdjjqfqpqmmqgqgqggzmmnncrrpgglgqlqclqlsqqwnnfntnzttrnnmtmvvfppbrprzpzzdzvvtctnncpclpccsbbswwcscjssvhvhhqggzmgmqgmgwwgcwgccrllzhzzlzlbljbllmqqpjqqhrhqhjhbbjmjmhmddmwmcmvvmbbmvbmmznnwvwlwtllhwlwgwpgplgpgmgngjgglbglgmllvvlttgrrrlsrllghlggjdjwwfjwfjfvhjmgqnwhwpbdtzrphsqbmmvscslhbdzffsfshgsdjbqbwlgmrtschcnfhdlnndsvpwmwttfglpghhznmgfcjsdlwhnmfqvmpvhgpnnwtjfztbmtprqhsqtjwzhwcqjtjbtqwlcldnvggrwddmpllwnrqwdljwzfzqwcdwgqwvnthnrpcsfwrmqvbzjvzqnmdnfgtbzgtnrvblfwmhdsddgbffnjzvjzfpwglctpqhnqdvtblcchrlmndzhlsczgnsmnbwgnjngnjtlrdpfhqjrwcrqvcpspbtwcvgvvmpnwqjjpdpnslmcrcjnjmhqmrmfbcmrcmpbcbhpcvwqwflljfpgdvqhgdwgcphjqfnqzjjpsqnbtfzhftjtfcbhhcmmlwcfznsflfpphprrgvqwfgjcwfgjfsghzcbqrldwrjlzlbjhpgrbmgdpgzmfsqsphqbbslwwpzspccrhcfrgcjlfwhlcmzdcltbbpcrzglqgqntpwtmgstqlmcsqqbsqgmsmfznwcrfdgvsmnfqmwtsvqvlhwwjlrlhnsvcnrtwwmrjcgfncvlrcqrllndlvmrjpfjpgrrjcwhsqvlbtnlqgwjjqzwcvtvlnfnmqqshbcnqtcbvnwtwbfdgqmvnpmjhlsfdntfwwntvsrrsmspzqmglfnprjtdbmbgnplzzclsjpnzwdhcbhpfnqrgmgqtpfhgnfbqhrpmznbrshjhntzctslwhtgtjvpqhntmchhtncfjmbzcgnpcbpmldrtnpvrzqfftbjjcjlpwwgvmnstjghftcczjzfsftgzpfhbspqmrbfhcdfmqbrgrbsmjvgpbrnvbblwwvqzzpmqrspzvzppjfbgfftdvsdvmrjzhfslptzmgndnqqgmrrfnbbpvbmvpngwjhzvfbwfnzlrgwffvjsfdldfgchfjmnzfnzhwrwttrzlrhmnwvjjdqfmbpfllhrgmddjgnwjnbqwjnslcrdjrmnldcpsgzjpdhrpdfwhbvwhwnhcsmwcwstvqrcrqsnvjrzljfgbljfszchbsqnldgntvcscwqqmpnlwtlfmswtmvrlpzgbrjhtgjgpnhggnprpvwfqpjffqhtfvpnrptgrtwzzlvplgnfjmqphgmnssccrdndqgpljtwtntshrpgsjcdrpmccjnjdgmpmzbfhqjzphcswtwvvqcrwsjhtdqgrhqjmjjcrblpswcblnpzvfztqtbpgjcgngqmwrjtlmhvlsbmrdzwlgqlfqcqnsnjcnddssqbftjvnlgcwwfcgdpdmqrdsjmcnzrfrpnvjmbsltpzwjhjzqqvbgrltczbgvcpwdzqsvhddsbjgjgcmnldrfhnhddlvjcvsnghprjwlghhtghldcqsdcdgnmbcjglvjjvvlbhzczlmjsdqtdpzdtvfztgsdfjsdtfchvzcgvhjnnncmsrfvvmcsjjdftmlpczgvtwngssqmzlmsrrsrbhhhrnwqhmpcdvqmdsvvtsgsqfdcpgsdgzvmbzpbpgtcbshnvdzlmpnwmqrvnmrjprmvppjwfbjhlhzsfhqqzmpbclqvsvfrcqwprrcvqcbbwvnqfwnrgjhlwmgzpfspqrvqrhmqnwvzjrhvvgdgswlvzjjhjtdctlthlpzqhjvwwbpsclpgflcnsdshrqbhmczcwljqlndfnfrcdgmptpsltrcjccnpdchgnswdcpsslcslcjznzpgfhznhbgqhdqvddmqzdnmpshhdcjrsmfjllhfvjvmzzhzrvlbpzqngwmlwcmqnppqzncvjshfrpjlptvnqfrfcrfnbhwhpdqqvjhsqvsmprtgfrddwzjzlwhhqvjpfrwgwvwpszzsfzwjtwngdjfllhjrmqjtmvwsvggnswpqpjbtcrnhhhlzbrvhjdstnpctjlgsffrrbfdvjzhwsgthgfsqnvqdcjffsttlrjnhtqqdpfqpjtdgfwcdwzmwfvqgglsrmmqwbszclpzwldwcswpwfwldrfmmdndcptjbmnvgcpntqcdrcffvgnlpjmcqjpfmbmwjfpqzbzhqtqbzsghbnfvhphfzzhfznttpfrqwpmzjchpzzrdclhdltlqbjmjdfdjqlqbwptsghcnvtdscwgpqnlhhvsvglplhlrwpnzmdbsbrlhmpczzfz";
-// start with window, 0, 0
-// record the character
-// move the window right
-// check if the character was in the window
-// if was in the window, then move window after the position it was seen
-// in the window keep track only the smaller index
-pub fn solve(signal: &str, window_size: usize) -> usize {
+// Every window size keeps its own byte counters over the same ring buffer of
+// the most recent bytes. Pushing a byte adds it to each window and drops the
+// byte that just fell out of it, so each byte costs O(windows) no matter how
+// long the stream is. `repeated` counts byte values that occur more than once
+// in the window, a marker is wherever it drops to 0 on a full window.
+struct Window {
+    size: usize,
+    counts: [u32; 256],
+    repeated: usize,
+}
 
-    let mut indices: HashMap<char, usize> = HashMap::new();
-    let mut start: usize = 0;
-    let mut end: usize = 0;
+impl Window {
+    fn new(size: usize) -> Window {
+        Window {
+            size,
+            counts: [0; 256],
+            repeated: 0,
+        }
+    }
 
-    for (index, byte) in signal.chars().enumerate() {
+    fn add(&mut self, byte: u8) {
+        let count = &mut self.counts[byte as usize];
+        *count += 1;
+        if *count == 2 {
+            self.repeated += 1;
+        }
+    }
 
-        match indices.get(&byte) {
-            Some(recorded_index) => {
-                let next_start = recorded_index + 1;
-                for i in start..next_start {
-                    indices.remove(&signal.chars().nth(i).unwrap());
-                }
-                start = next_start;
-                indices.insert(byte, index);
-            },
-            None => {
-                indices.insert(byte, index);
+    fn remove(&mut self, byte: u8) {
+        let count = &mut self.counts[byte as usize];
+        if *count == 2 {
+            self.repeated -= 1;
+        }
+        *count -= 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    pub window_size: usize,
+    // number of bytes read when the marker was complete, the puzzle answer
+    pub position: usize,
+}
+
+pub struct Detector {
+    windows: Vec<Window>,
+    recent: Vec<u8>,
+    position: usize,
+}
+
+impl Detector {
+    pub fn new(window_sizes: &[usize]) -> Detector {
+        assert!(
+            !window_sizes.is_empty() && window_sizes.iter().all(|&size| size > 0),
+            "Window sizes have to be positive"
+        );
+        let longest = window_sizes.iter().copied().max().unwrap();
+
+        Detector {
+            windows: window_sizes.iter().map(|&size| Window::new(size)).collect(),
+            recent: vec![0; longest],
+            position: 0,
+        }
+    }
+
+    // Feeds the next byte of the stream, calling `found` for every window
+    // which ends with a marker at this byte.
+    pub fn push(&mut self, byte: u8, mut found: impl FnMut(Marker)) {
+        let slot = self.position % self.recent.len();
+
+        for window in self.windows.iter_mut() {
+            if self.position >= window.size {
+                let outgoing = (self.position - window.size) % self.recent.len();
+                window.remove(self.recent[outgoing]);
             }
         }
 
-        end += 1;
-        println!("Start: {}, end: {}, map: {}", start, end, indices.len());
-        if indices.len() == window_size {
-            println!("Found ya! {}", end);
-            return end;
+        self.recent[slot] = byte;
+        self.position += 1;
+
+        for window in self.windows.iter_mut() {
+            window.add(byte);
+            if self.position >= window.size && window.repeated == 0 {
+                found(Marker {
+                    window_size: window.size,
+                    position: self.position,
+                });
+            }
+        }
+    }
+}
+
+// Lazily reports every marker in a stream, reading it in chunks. Whitespace
+// isn't part of the signal, but it still counts towards the positions, so
+// they're always offsets into the stream.
+pub struct Markers<R: Read> {
+    reader: R,
+    detector: Detector,
+    buffer: Box<[u8]>,
+    pending: VecDeque<Marker>,
+    // bytes read so far, whitespace included
+    offset: usize,
+    done: bool,
+}
+
+impl<R: Read> Markers<R> {
+    pub fn new(reader: R, window_sizes: &[usize]) -> Markers<R> {
+        Markers {
+            reader,
+            detector: Detector::new(window_sizes),
+            buffer: vec![0; 64 * 1024].into_boxed_slice(),
+            pending: VecDeque::new(),
+            offset: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for Markers<R> {
+    type Item = io::Result<Marker>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            let read = match self.reader.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.done = true;
+                    break;
+                }
+                Ok(read) => read,
+                Err(why) if why.kind() == io::ErrorKind::Interrupted => continue,
+                Err(why) => {
+                    self.done = true;
+                    return Some(Err(why));
+                }
+            };
+
+            for &byte in &self.buffer[..read] {
+                self.offset += 1;
+                if byte.is_ascii_whitespace() {
+                    continue;
+                }
+                let (pending, position) = (&mut self.pending, self.offset);
+                self.detector.push(byte, |marker| pending.push_back(Marker { position, ..marker }));
+            }
         }
+
+        self.pending.pop_front().map(Ok)
     }
+}
+
+// Position of the first marker, or 0 when there is none.
+pub fn solve(signal: &str, window_size: usize) -> usize {
+    Markers::new(signal.as_bytes(), &[window_size])
+        .next()
+        .map(|marker| marker.unwrap().position)
+        .unwrap_or(0)
+}
+
+pub fn part1() {
+    println!("{}", solve(INPUT_SIGNAL, 4));
+}
 
-    return 0;
+pub fn part2() {
+    println!("{}", solve(INPUT_SIGNAL, 14));
 }
 
+#[cfg(test)]
 mod tests {
-    use super::solve;
+    use super::*;
 
     #[test]
     fn sanity() {
@@ -73,4 +202,80 @@ mod tests {
         let signal = "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg";
         assert_eq!(29, solve(signal, 14))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn every_marker_for_every_window() {
+        let markers: Vec<Marker> = Markers::new("abcabcd".as_bytes(), &[3, 4])
+            .map(|marker| marker.unwrap())
+            .collect();
+
+        let positions = |size| -> Vec<usize> {
+            markers
+                .iter()
+                .filter(|m| m.window_size == size)
+                .map(|m| m.position)
+                .collect()
+        };
+        assert_eq!(vec![3, 4, 5, 6, 7], positions(3));
+        assert_eq!(vec![7], positions(4));
+    }
+
+    #[test]
+    fn whitespace_counts_towards_position() {
+        let markers: Vec<Marker> = Markers::new("ab\ncd\n".as_bytes(), &[4])
+            .map(|marker| marker.unwrap())
+            .collect();
+
+        assert_eq!(vec![Marker { window_size: 4, position: 5 }], markers);
+    }
+
+    #[test]
+    #[should_panic(expected = "Window sizes have to be positive")]
+    fn empty_window() {
+        Detector::new(&[0, 4]);
+    }
+
+    #[test]
+    fn no_marker() {
+        assert_eq!(0, solve("aaaaaaaa", 4));
+        assert_eq!(0, solve("abc", 4));
+    }
+
+    // reads one byte at a time so markers straddle the buffer boundaries
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&byte, rest)) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_matches_input_signal() {
+        let first = |sizes: &[usize]| -> Vec<Marker> {
+            let mut firsts: Vec<Marker> = Vec::new();
+            for marker in Markers::new(Trickle(INPUT_SIGNAL.as_bytes()), sizes) {
+                let marker = marker.unwrap();
+                if !firsts.iter().any(|m| m.window_size == marker.window_size) {
+                    firsts.push(marker);
+                }
+            }
+            firsts
+        };
+
+        let both = first(&[4, 14]);
+        assert_eq!(
+            vec![solve(INPUT_SIGNAL, 4), solve(INPUT_SIGNAL, 14)],
+            vec![both[0].position, both[1].position]
+        );
+        assert_eq!(first(&[4]), vec![both[0]]);
+        assert_eq!(first(&[14]), vec![both[1]]);
+    }
+}
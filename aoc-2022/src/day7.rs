@@ -1,242 +1,797 @@
-use regex::Regex;
-use std::cell::RefCell;
-use std::collections::VecDeque;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::rc::Rc;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
 
 #[derive(Debug)]
-struct Entry {
-    name: String,
-    entries: Vec<Rc<RefCell<Entry>>>,
-    size: usize,
+pub enum Node {
+    File {
+        name: String,
+        parent: NodeId,
+        size: usize,
+    },
+    Directory {
+        name: String,
+        // only the root has no parent
+        parent: Option<NodeId>,
+        children: BTreeMap<String, NodeId>,
+    },
 }
 
-impl Entry {
-    fn print(&self) -> String {
-        if self.entries.len() == 0 {
-            return self.name.clone();
-        } else {
-            return String::from("[")
-                + &self.name
-                + ": "
-                + &self
-                    .entries
-                    .iter()
-                    .map(|tn| tn.borrow().name.clone())
-                    .collect::<Vec<String>>()
-                    .join(",")
-                + "]";
+impl Node {
+    pub fn name(&self) -> &str {
+        match self {
+            Node::File { name, .. } | Node::Directory { name, .. } => name,
         }
     }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        match self {
+            Node::File { parent, .. } => Some(*parent),
+            Node::Directory { parent, .. } => *parent,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self, Node::Directory { .. })
+    }
 }
 
-fn calculate_directory_sizes(cell: Rc<RefCell<Entry>>, part1: &mut usize) -> usize {
-    let mut entry = cell.borrow_mut();
+#[derive(Debug, PartialEq, Eq)]
+pub enum FsError {
+    NotADirectory(String),
+    // a file and a directory with the same name
+    Conflict(String),
+}
 
-    let mut calculated_size = 0;
-    for entry in &entry.entries {
-        calculated_size += calculate_directory_sizes(Rc::clone(entry), part1);
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FsError::NotADirectory(path) => write!(f, "{} is not a directory", path),
+            FsError::Conflict(path) => write!(f, "{} already exists with a different type", path),
+        }
     }
+}
 
-    if entry.entries.len() > 0 {
-        entry.size = calculated_size;
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShellError {
+    UnknownCommand { line: usize, command: String },
+    NoSuchDirectory { line: usize, path: String },
+    // a line of output which doesn't follow an `ls`
+    UnexpectedOutput { line: usize, output: String },
+    MalformedEntry { line: usize, entry: String },
+    Fs { line: usize, error: FsError },
+}
 
-        println!("name: {}, size: {}", entry.name, entry.size);
-        if entry.size <= 100000 {
-            *part1 += entry.size;
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::UnknownCommand { line, command } => {
+                write!(f, "line {}: unknown command '{}'", line, command)
+            }
+            ShellError::NoSuchDirectory { line, path } => {
+                write!(f, "line {}: no such directory '{}'", line, path)
+            }
+            ShellError::UnexpectedOutput { line, output } => {
+                write!(f, "line {}: output '{}' without a command", line, output)
+            }
+            ShellError::MalformedEntry { line, entry } => {
+                write!(f, "line {}: malformed ls entry '{}'", line, entry)
+            }
+            ShellError::Fs { line, error } => write!(f, "line {}: {}", line, error),
         }
     }
+}
+
+// A directory together with the size of everything underneath it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryUsage {
+    pub id: NodeId,
+    pub path: String,
+    pub size: usize,
+}
+
+#[derive(Debug)]
+pub struct FileSystem {
+    // nodes are only ever appended, so a child always comes after its parent
+    nodes: Vec<Node>,
+}
 
-    return entry.size;
+impl Default for FileSystem {
+    fn default() -> Self {
+        FileSystem::new()
+    }
 }
 
-fn parse(reader: io::Lines<io::BufReader<File>>) -> Rc<RefCell<Entry>> {
-    let mut stack = VecDeque::new();
+impl FileSystem {
+    pub fn new() -> FileSystem {
+        FileSystem {
+            nodes: vec![Node::Directory {
+                name: "/".to_string(),
+                parent: None,
+                children: BTreeMap::new(),
+            }],
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let children = match self.node(id) {
+            Node::Directory { children, .. } => Some(children.values().copied()),
+            Node::File { .. } => None,
+        };
+
+        children.into_iter().flatten()
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.node(current).parent() {
+            names.push(self.node(current).name());
+            current = parent;
+        }
+        names.reverse();
+
+        format!("/{}", names.join("/"))
+    }
+
+    // Resolves `path` relative to `from`, absolute paths start at the root.
+    pub fn resolve(&self, from: NodeId, path: &str) -> Option<NodeId> {
+        let mut current = if path.starts_with('/') { self.root() } else { from };
+
+        for component in path.split('/') {
+            current = match component {
+                "" | "." => current,
+                // the root is its own parent, just like in a shell
+                ".." => self.node(current).parent().unwrap_or(current),
+                name => match self.node(current) {
+                    Node::Directory { children, .. } => *children.get(name)?,
+                    Node::File { .. } => return None,
+                },
+            };
+        }
+
+        Some(current)
+    }
+
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        self.resolve(self.root(), path)
+    }
+
+    fn insert(&mut self, parent: NodeId, node: Node) -> Result<NodeId, FsError> {
+        let next_id = NodeId(self.nodes.len());
+        let existing = match &self.nodes[parent.0] {
+            Node::Directory { children, .. } => children.get(node.name()).copied(),
+            Node::File { .. } => return Err(FsError::NotADirectory(self.path(parent))),
+        };
+
+        if let Some(id) = existing {
+            return match (&mut self.nodes[id.0], node) {
+                (Node::Directory { .. }, Node::Directory { .. }) => Ok(id),
+                (Node::File { size, .. }, Node::File { size: new_size, .. }) => {
+                    *size = new_size;
+                    Ok(id)
+                }
+                _ => Err(FsError::Conflict(self.path(id))),
+            };
+        }
+
+        if let Node::Directory { children, .. } = &mut self.nodes[parent.0] {
+            children.insert(node.name().to_string(), next_id);
+        }
+        self.nodes.push(node);
 
-    let cd_regex = Regex::new(r"\$ cd (?P<directory>.*)").unwrap();
-    let dir_regex = Regex::new(r"dir (?P<directory>.*)").unwrap();
-    let file_regex = Regex::new(r"(?P<filesize>\d+) (?P<filename>.*)").unwrap();
+        Ok(next_id)
+    }
+
+    // Creating something which already exists returns the existing node.
+    pub fn create_dir(&mut self, parent: NodeId, name: &str) -> Result<NodeId, FsError> {
+        self.insert(
+            parent,
+            Node::Directory {
+                name: name.to_string(),
+                parent: Some(parent),
+                children: BTreeMap::new(),
+            },
+        )
+    }
 
-    let root = Rc::new(RefCell::new(Entry {
-        name: "/".to_string(),
-        entries: vec![],
-        size: 0,
-    }));
+    pub fn create_file(&mut self, parent: NodeId, name: &str, size: usize) -> Result<NodeId, FsError> {
+        self.insert(
+            parent,
+            Node::File {
+                name: name.to_string(),
+                parent,
+                size,
+            },
+        )
+    }
+
+    // Recursive sizes of every node, indexed by `NodeId`.
+    fn sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::File { size, .. } => *size,
+                Node::Directory { .. } => 0,
+            })
+            .collect();
+
+        for id in (1..self.nodes.len()).rev() {
+            let parent = self.nodes[id].parent().unwrap();
+            sizes[parent.0] += sizes[id];
+        }
+
+        sizes
+    }
+
+    pub fn size(&self, id: NodeId) -> usize {
+        self.sizes()[id.0]
+    }
+
+    // Every directory, parents before their children and siblings by name.
+    pub fn directories(&self) -> impl Iterator<Item = DirectoryUsage> + '_ {
+        let sizes = self.sizes();
+        let mut stack = vec![self.root()];
+
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            let mut subdirectories: Vec<NodeId> = self
+                .children(id)
+                .filter(|child| self.node(*child).is_dir())
+                .collect();
+            subdirectories.reverse();
+            stack.extend(subdirectories);
+
+            Some(DirectoryUsage {
+                id,
+                path: self.path(id),
+                size: sizes[id.0],
+            })
+        })
+    }
 
-    let mut next_current_dir: Option<Rc<RefCell<Entry>>> = None;
-    let mut current_dir = root.clone();
+    // Replays a shell transcript of `cd` and `ls` commands with their output.
+    pub fn parse(transcript: &str) -> Result<FileSystem, ShellError> {
+        let mut fs = FileSystem::new();
+        let mut cwd = fs.root();
+        let mut listing = false;
 
-    let mut browsing = false;
-    for line in reader {
-        if let Ok(l) = line {
-            if l == "" || l.starts_with("$ cd /") {
+        for (idx, raw) in transcript.lines().enumerate() {
+            let line = idx + 1;
+            let l = raw.trim();
+            if l.is_empty() {
                 continue;
             }
-            if l.starts_with("$ ls") {
-                browsing = true;
+
+            if let Some(command) = l.strip_prefix('$') {
+                let mut args = command.split_whitespace();
+                listing = false;
+                match (args.next(), args.next()) {
+                    (Some("cd"), Some(path)) => {
+                        cwd = fs
+                            .resolve(cwd, path)
+                            .filter(|id| fs.node(*id).is_dir())
+                            .ok_or(ShellError::NoSuchDirectory {
+                                line,
+                                path: path.to_string(),
+                            })?;
+                    }
+                    (Some("ls"), None) => listing = true,
+                    _ => {
+                        return Err(ShellError::UnknownCommand {
+                            line,
+                            command: command.trim().to_string(),
+                        })
+                    }
+                }
                 continue;
             }
-            if browsing && l.starts_with("$") {
-                browsing = false;
-            }
 
-            match next_current_dir {
-                Some(ref next) => {
-                    current_dir = Rc::clone(&next);
-                    next_current_dir = None;
-                }
-                None => {},
+            if !listing {
+                return Err(ShellError::UnexpectedOutput {
+                    line,
+                    output: l.to_string(),
+                });
             }
 
-            if browsing {
-                if l.starts_with("dir") {
-                    let cap = dir_regex.captures(&l).unwrap();
-                    let dir_name = cap.name("directory").unwrap().as_str();
-
-                    let mut directory = current_dir.borrow_mut();
-
-                    // maybe they can repeat itself
-                    directory.entries.push(Rc::new(RefCell::new(Entry {
-                        name: dir_name.to_string(),
-                        entries: vec![],
-                        size: 0,
-                    })));
-                } else {
-                    let cap = file_regex.captures(&l).unwrap();
-                    let file_name = cap.name("filename").unwrap().as_str();
-                    let file_size: usize = cap.name("filesize").unwrap().as_str().parse().unwrap();
-
-                    let mut directory = current_dir.borrow_mut();
-
-                    // maybe they can repeat itself
-                    directory.entries.push(Rc::new(RefCell::new(Entry {
-                        name: file_name.to_string(),
-                        entries: vec![],
-                        size: file_size,
-                    })));
+            let created = match l.split_once(' ') {
+                Some(("dir", name)) => fs.create_dir(cwd, name),
+                Some((size, name)) => match size.parse() {
+                    Ok(size) => fs.create_file(cwd, name, size),
+                    Err(_) => {
+                        return Err(ShellError::MalformedEntry {
+                            line,
+                            entry: l.to_string(),
+                        })
+                    }
+                },
+                None => {
+                    return Err(ShellError::MalformedEntry {
+                        line,
+                        entry: l.to_string(),
+                    })
                 }
-            }
+            };
+            created.map_err(|error| ShellError::Fs { line, error })?;
+        }
 
-            if l.starts_with("$ cd") {
-                // println!("Xd: {}", l);
-                let cap = cd_regex.captures(&l).unwrap();
-                let dir_name = cap.name("directory").unwrap().as_str();
+        Ok(fs)
+    }
+}
 
-                if dir_name == ".." {
-                    current_dir = stack.pop_back().unwrap();
-                } else {
-                    let mut directory = current_dir.borrow();
+impl FileSystem {
+    // Listing in the same format as the puzzle description, with the size of
+    // directories added in.
+    pub fn tree(&self) -> String {
+        let sizes = self.sizes();
+        let mut lines = Vec::new();
+        let mut stack = vec![(self.root(), 0)];
+
+        while let Some((id, depth)) = stack.pop() {
+            let description = match self.node(id) {
+                Node::File { size, .. } => format!("file, size={}", size),
+                Node::Directory { .. } => format!("dir, size={}", sizes[id.0]),
+            };
+            lines.push(format!("{}- {} ({})", "  ".repeat(depth), self.node(id).name(), description));
+
+            let children: Vec<NodeId> = self.children(id).collect();
+            stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+        }
 
-                    for nested_dir in &directory.entries {
-                        let x = nested_dir.borrow();
-                        if x.name == dir_name {
-                            stack.push_back(Rc::clone(&current_dir));
+        lines.join("\n")
+    }
 
-                            next_current_dir = Some(Rc::clone(nested_dir));
-                        }
-                    }
-                }
+    // Like `du -h | sort -rh`, the biggest directories first.
+    pub fn du(&self) -> String {
+        self.largest_directories(usize::MAX)
+            .iter()
+            .map(|dir| format!("{}\t{}", human_size(dir.size), dir.path))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn largest_directories(&self, n: usize) -> Vec<DirectoryUsage> {
+        let mut dirs: Vec<DirectoryUsage> = self.directories().collect();
+        dirs.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        dirs.truncate(n);
+
+        dirs
+    }
+
+    fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut current = self.node(id).parent();
+        while let Some(parent) = current {
+            if parent == ancestor {
+                return true;
             }
+            current = self.node(parent).parent();
         }
+
+        false
     }
-    return root;
 }
 
+// Rounds up like `du -h`: 584, 4.5K, 92M...
+pub fn human_size(size: usize) -> String {
+    let units = ["K", "M", "G", "T", "P"];
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        format!("{:.1}{}", (value * 10.0).ceil() / 10.0, units[unit])
+    } else {
+        format!("{}{}", value.ceil(), units[unit])
+    }
+}
 
-const required_space: usize = 30000000;
-const total_space: usize = 70000000;
+#[derive(Debug, Clone, Copy)]
+pub struct Disk {
+    pub total_space: usize,
+    pub required_space: usize,
+}
 
-fn find_smallest_directory(
-    cell: Rc<RefCell<Entry>>,
-    currently_unused: usize,
-    smallest_dir: &mut Rc<RefCell<Entry>>,
-) {
-    let mut entry = cell.borrow();
+impl Disk {
+    pub const DEVICE: Disk = Disk {
+        total_space: 70000000,
+        required_space: 30000000,
+    };
 
-    if entry.size < smallest_dir.borrow().size && currently_unused + entry.size >= required_space {
-        *smallest_dir = Rc::clone(&cell);
-        println!("Smallest: {}, {} ", entry.size, entry.name)
+    // How much has to be deleted before there is enough space left.
+    pub fn space_to_free(&self, fs: &FileSystem) -> usize {
+        let unused = self.total_space.saturating_sub(fs.size(fs.root()));
+
+        self.required_space.saturating_sub(unused)
     }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanupPlan {
+    pub directories: Vec<DirectoryUsage>,
+    pub freed: usize,
+}
+
+// Searches for the directories to delete which free the least space while
+// still freeing enough. Nested directories are never picked together, as
+// deleting the parent already deletes the child.
+struct Planner<'a> {
+    fs: &'a FileSystem,
+    // biggest first, so the search finds good plans early
+    candidates: Vec<DirectoryUsage>,
+    // sum of the candidates from the index onwards, to give up on branches
+    // which can't free enough anymore
+    remaining: Vec<usize>,
+    needed: usize,
+    chosen: Vec<usize>,
+    best: CleanupPlan,
+}
+
+impl Planner<'_> {
+    fn search(&mut self, idx: usize, freed: usize) {
+        if freed >= self.needed {
+            if freed < self.best.freed
+                || (freed == self.best.freed && self.chosen.len() < self.best.directories.len())
+            {
+                self.best = CleanupPlan {
+                    directories: self.chosen.iter().map(|c| self.candidates[*c].clone()).collect(),
+                    freed,
+                };
+            }
+            return;
+        }
 
-    for entry in &entry.entries {
-        let r = entry.borrow();
-        if r.entries.len() == 0 {
-            continue;
+        if self.best.freed == self.needed
+            || idx == self.candidates.len()
+            || freed + self.remaining[idx] < self.needed
+        {
+            return;
         }
-        find_smallest_directory(Rc::clone(entry), currently_unused, smallest_dir)
+
+        let candidate = &self.candidates[idx];
+        let nested = self.chosen.iter().any(|c| {
+            let other = self.candidates[*c].id;
+            self.fs.is_ancestor(other, candidate.id) || self.fs.is_ancestor(candidate.id, other)
+        });
+        if !nested && freed + candidate.size <= self.best.freed {
+            let size = candidate.size;
+            self.chosen.push(idx);
+            self.search(idx + 1, freed + size);
+            self.chosen.pop();
+        }
+
+        self.search(idx + 1, freed);
     }
 }
 
-pub fn solve() {
-    let file = match File::open("inputs/7_input") {
-        Err(why) => panic!("Couldn't open file {}", why),
-        Ok(file) => file,
+// None when even deleting everything doesn't free enough space.
+pub fn plan_cleanup(fs: &FileSystem, disk: Disk) -> Option<CleanupPlan> {
+    let needed = disk.space_to_free(fs);
+    if needed == 0 {
+        return Some(CleanupPlan {
+            directories: Vec::new(),
+            freed: 0,
+        });
+    }
+
+    // the best single directory is the plan to beat
+    let single = find_smallest_directory(fs, disk)?;
+    let mut candidates: Vec<DirectoryUsage> = fs
+        .directories()
+        .filter(|dir| dir.size < single.size)
+        .collect();
+    candidates.sort_by_key(|dir| Reverse(dir.size));
+
+    let mut remaining = vec![0; candidates.len() + 1];
+    for idx in (0..candidates.len()).rev() {
+        remaining[idx] = remaining[idx + 1] + candidates[idx].size;
+    }
+
+    let mut planner = Planner {
+        fs,
+        candidates,
+        remaining,
+        needed,
+        chosen: Vec::new(),
+        best: CleanupPlan {
+            freed: single.size,
+            directories: vec![single],
+        },
     };
+    planner.search(0, 0);
 
-    let reader = io::BufReader::new(file).lines();
-    let root = parse(reader);
+    Some(planner.best)
+}
 
-    println!("x: {}", root.borrow().print());
+fn part1(fs: &FileSystem) -> usize {
+    fs.directories()
+        .map(|dir| dir.size)
+        .filter(|size| *size <= 100000)
+        .sum()
+}
+
+fn find_smallest_directory(fs: &FileSystem, disk: Disk) -> Option<DirectoryUsage> {
+    let needed = disk.space_to_free(fs);
+
+    fs.directories()
+        .filter(|dir| dir.size >= needed)
+        .min_by_key(|dir| dir.size)
+}
+
+fn load(filename: &str) -> FileSystem {
+    let transcript = match fs::read_to_string(filename) {
+        Err(why) => panic!("Couldn't open file {}", why),
+        Ok(transcript) => transcript,
+    };
+
+    match FileSystem::parse(&transcript) {
+        Err(why) => panic!("Couldn't replay the transcript: {}", why),
+        Ok(fs) => fs,
+    }
+}
 
-    let mut part1: usize = 0;
-    calculate_directory_sizes(Rc::clone(&root), &mut part1);
+pub fn solve() {
+    let fs = load("inputs/7_input");
 
-    let mut smallest_dir = Rc::clone(&root);
-    let currently_unused = total_space - Rc::clone(&root).borrow().size;
-    find_smallest_directory(Rc::clone(&root), currently_unused, &mut smallest_dir);
+    println!("{}", part1(&fs));
+    if let Some(dir) = find_smallest_directory(&fs, Disk::DEVICE) {
+        println!("{} {}", dir.path, dir.size);
+    }
+    if let Some(plan) = plan_cleanup(&fs, Disk::DEVICE) {
+        for dir in &plan.directories {
+            println!("rm -r {}\t{}", dir.path, human_size(dir.size));
+        }
+        println!("frees {}", plan.freed);
+    }
+}
 
-    println!("{}", part1);
+// Lists the whole file system and what takes up the space, or just the
+// size of `path` when there's one.
+pub fn report(path: Option<&str>) {
+    let fs = load("inputs/7_input");
+
+    match path {
+        Some(path) => match fs.lookup(path) {
+            Some(id) => println!("{}\t{}", human_size(fs.size(id)), path),
+            None => println!("There's no {}", path),
+        },
+        None => {
+            println!("{}\n", fs.tree());
+            println!("{}", fs.du());
+        }
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k
+";
+
     #[test]
     fn sanity_algorithm() {
-        let root = Rc::new(RefCell::new(Entry {
-            name: "/".to_string(),
-            entries: vec![
-                Rc::new(RefCell::new(Entry {
-                    name: "a".to_string(),
-                    entries: vec![
-                        Rc::new(RefCell::new(Entry {
-                            name: "e".to_string(),
-                            entries: vec![Rc::new(RefCell::new(Entry {
-                                name: "i".to_string(),
-                                entries: vec![],
-                                size: 584,
-                            }))],
-                            size: 0,
-                        })),
-                        Rc::new(RefCell::new(Entry {
-                            name: "f".to_string(),
-                            entries: vec![],
-                            size: 29116,
-                        })),
-                        Rc::new(RefCell::new(Entry {
-                            name: "g".to_string(),
-                            entries: vec![],
-                            size: 2557,
-                        })),
-                        Rc::new(RefCell::new(Entry {
-                            name: "h.lst".to_string(),
-                            entries: vec![],
-                            size: 62596,
-                        })),
-                    ],
-                    size: 0,
-                })),
-                Rc::new(RefCell::new(Entry {
-                    name: "b.txt".to_string(),
-                    entries: vec![],
-                    size: 14848514,
-                })),
+        let mut fs = FileSystem::new();
+        let a = fs.create_dir(fs.root(), "a").unwrap();
+        let e = fs.create_dir(a, "e").unwrap();
+        fs.create_file(e, "i", 584).unwrap();
+        fs.create_file(a, "f", 29116).unwrap();
+        fs.create_file(a, "g", 2557).unwrap();
+        fs.create_file(a, "h.lst", 62596).unwrap();
+        fs.create_file(fs.root(), "b.txt", 14848514).unwrap();
+
+        assert_eq!(95437, part1(&fs));
+    }
+
+    #[test]
+    fn example() {
+        let fs = FileSystem::parse(EXAMPLE).unwrap();
+
+        assert_eq!(95437, part1(&fs));
+        assert_eq!(48381165, fs.size(fs.root()));
+        let smallest = find_smallest_directory(&fs, Disk::DEVICE).unwrap();
+        assert_eq!(("/d".to_string(), 24933642), (smallest.path, smallest.size));
+    }
+
+    #[test]
+    fn directories_with_sizes() {
+        let fs = FileSystem::parse(EXAMPLE).unwrap();
+        let dirs: Vec<(String, usize)> = fs.directories().map(|d| (d.path, d.size)).collect();
+
+        assert_eq!(
+            vec![
+                ("/".to_string(), 48381165),
+                ("/a".to_string(), 94853),
+                ("/a/e".to_string(), 584),
+                ("/d".to_string(), 24933642),
             ],
-            size: 0,
-        }));
-        let mut part1: usize = 0;
-        calculate_directory_sizes(root, &mut part1);
-        assert_eq!(95437, part1);
+            dirs
+        );
+    }
+
+    #[test]
+    fn path_lookup() {
+        let fs = FileSystem::parse(EXAMPLE).unwrap();
+
+        let i = fs.lookup("/a/e/i").unwrap();
+        assert_eq!("/a/e/i", fs.path(i));
+        assert!(matches!(fs.node(i), Node::File { size: 584, .. }));
+        assert_eq!(Some(fs.root()), fs.lookup("/"));
+        assert_eq!(fs.lookup("/a"), fs.lookup("/a/e/.."));
+        assert_eq!(Some(fs.root()), fs.lookup("/.."));
+        assert_eq!(None, fs.lookup("/a/missing"));
+        assert_eq!(None, fs.lookup("/b.txt/x"));
+    }
+
+    #[test]
+    fn cd_root_and_repeated_ls() {
+        let fs = FileSystem::parse(
+            "$ cd /
+$ ls
+dir a
+$ cd a
+$ ls
+10 x
+$ ls
+10 x
+dir b
+$ cd /
+$ ls
+20 y
+dir a
+$ cd /a/b
+$ ls
+5 z
+",
+        )
+        .unwrap();
+
+        assert_eq!(35, fs.size(fs.root()));
+        assert_eq!(15, fs.size(fs.lookup("/a").unwrap()));
+        assert_eq!(2, fs.children(fs.lookup("/a").unwrap()).count());
+    }
+
+    #[test]
+    fn transcript_errors() {
+        assert_eq!(
+            Err(ShellError::UnknownCommand { line: 2, command: "rm -rf a".to_string() }),
+            FileSystem::parse("$ cd /\n$ rm -rf a").map(|_| ())
+        );
+        assert_eq!(
+            Err(ShellError::NoSuchDirectory { line: 1, path: "a".to_string() }),
+            FileSystem::parse("$ cd a").map(|_| ())
+        );
+        assert_eq!(
+            Err(ShellError::UnexpectedOutput { line: 2, output: "dir a".to_string() }),
+            FileSystem::parse("$ cd /\ndir a").map(|_| ())
+        );
+        assert_eq!(
+            Err(ShellError::MalformedEntry { line: 2, entry: "big file".to_string() }),
+            FileSystem::parse("$ ls\nbig file").map(|_| ())
+        );
+        assert_eq!(
+            Err(ShellError::Fs { line: 3, error: FsError::Conflict("/a".to_string()) }),
+            FileSystem::parse("$ ls\ndir a\n10 a").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn tree_view() {
+        let fs = FileSystem::parse(EXAMPLE).unwrap();
+
+        assert_eq!(
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)",
+            fs.tree()
+        );
+    }
+
+    #[test]
+    fn du_listing() {
+        let fs = FileSystem::parse(EXAMPLE).unwrap();
+
+        assert_eq!("47M\t/\n24M\t/d\n93K\t/a\n584\t/a/e", fs.du());
+        assert_eq!("4.4K", human_size(4500));
+        assert_eq!("1.0K", human_size(1024));
+        assert_eq!(
+            vec!["/", "/d"],
+            fs.largest_directories(2).iter().map(|d| d.path.as_str()).collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn cleanup_plan() {
+        let fs = FileSystem::parse(EXAMPLE).unwrap();
+
+        let plan = plan_cleanup(&fs, Disk::DEVICE).unwrap();
+        assert_eq!(24933642, plan.freed);
+        assert_eq!(vec!["/d"], plan.directories.iter().map(|d| d.path.as_str()).collect::<Vec<&str>>());
+
+        // a smaller disk only needs a few small directories gone
+        let disk = Disk {
+            total_space: 48381165 + 1000,
+            required_space: 1000 + 94000,
+        };
+        let plan = plan_cleanup(&fs, disk).unwrap();
+        assert_eq!(94853, plan.freed);
+        assert_eq!(
+            vec!["/a"],
+            plan.directories.iter().map(|d| d.path.as_str()).collect::<Vec<&str>>()
+        );
+
+        assert_eq!(0, plan_cleanup(&fs, Disk { total_space: 100000000, required_space: 10 }).unwrap().freed);
+        assert_eq!(None, plan_cleanup(&fs, Disk { total_space: 10, required_space: 100000000 }));
+    }
+
+    #[test]
+    fn cleanup_plan_combines_directories() {
+        let mut fs = FileSystem::new();
+        for (name, size) in [("a", 60), ("b", 50), ("c", 45), ("d", 30)] {
+            let dir = fs.create_dir(fs.root(), name).unwrap();
+            fs.create_file(dir, "data", size).unwrap();
+        }
+        let nested = fs.create_dir(fs.lookup("/a").unwrap(), "nested").unwrap();
+        fs.create_file(nested, "data", 20).unwrap();
+
+        // 205 used, 75 have to go: c + d frees exactly that, less than any single directory
+        let plan = plan_cleanup(&fs, Disk { total_space: 250, required_space: 120 }).unwrap();
+        assert_eq!(75, plan.freed);
+        assert_eq!(
+            vec!["/c", "/d"],
+            plan.directories.iter().map(|d| d.path.as_str()).collect::<Vec<&str>>()
+        );
     }
 }
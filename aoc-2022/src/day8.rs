@@ -1,350 +1,272 @@
-use std::cmp::max;
 use std::fs::File;
 use std::io::{self, BufRead};
 
 #[derive(Clone, Copy, Debug)]
 struct Tree {
-    x: usize,
-    y: usize,
     height: u32,
 }
 
 struct Grid {
     trees: Vec<Vec<Tree>>,
-    max_x: usize,
-    max_y: usize,
-}
-
-impl Grid {
-    fn print(&self) {
-        for y in 0..self.max_y {
-            for x in 0..self.max_x {
-                print!("{}", self.trees.get(y).unwrap().get(x).unwrap().height)
-            }
-            println!();
-        }
-    }
 }
 
 fn parse(reader: io::Lines<io::BufReader<File>>) -> Grid {
     let mut grid: Vec<Vec<Tree>> = Vec::new();
 
-    let mut current_y: usize = 0;
-    let mut current_x: usize = 0;
-    for line in reader {
-        if let Ok(l) = line {
-            let mut row: Vec<Tree> = Vec::new();
-            current_x = 0;
-
-            for char in l.chars() {
-                row.push(Tree {
-                    x: current_x,
-                    y: current_y,
-                    height: char.to_digit(10).unwrap(),
-                });
-
-                current_x += 1;
-            }
+    for l in reader.map_while(Result::ok) {
+        let mut row: Vec<Tree> = Vec::new();
 
-            grid.push(row);
+        for char in l.chars() {
+            row.push(Tree {
+                height: char.to_digit(10).unwrap(),
+            });
         }
 
-        current_y += 1;
+        grid.push(row);
     }
 
-    return Grid {
-        trees: grid,
-        max_x: current_x,
-        max_y: current_y,
-    };
+    Grid { trees: grid }
 }
 
-#[derive(Debug)]
-enum Direction {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
     Left,
     Right,
     Top,
     Down,
 }
 
-fn determine_max_height(tree: &Tree, grid: &Grid, direction: Direction) -> u32 {
-    return match direction {
-        Direction::Left => {
-            if tree.x == 0 {
-                tree.height
-            } else {
-                max(
-                    tree.height,
-                    determine_max_height(
-                        &grid.trees.get(tree.y).unwrap().get(tree.x - 1).unwrap(),
-                        grid,
-                        direction,
-                    ),
-                )
-            }
-        }
-        Direction::Right => {
-            if tree.x == grid.max_x - 1 {
-                tree.height
-            } else {
-                max(
-                    tree.height,
-                    determine_max_height(
-                        &grid.trees.get(tree.y).unwrap().get(tree.x + 1).unwrap(),
-                        grid,
-                        direction,
-                    ),
-                )
-            }
-        }
-        Direction::Down => {
-            if tree.y == grid.max_y - 1 {
-                tree.height
-            } else {
-                max(
-                    tree.height,
-                    determine_max_height(
-                        &grid.trees.get(tree.y + 1).unwrap().get(tree.x).unwrap(),
-                        grid,
-                        direction,
-                    ),
-                )
-            }
-        }
-        Direction::Top => {
-            if tree.y == 0 {
-                tree.height
-            } else {
-                max(
-                    tree.height,
-                    determine_max_height(
-                        &grid.trees.get(tree.y - 1).unwrap().get(tree.x).unwrap(),
-                        grid,
-                        direction,
-                    ),
-                )
-            }
-        }
-    };
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Top,
+        Direction::Down,
+    ];
+
+    fn bit(&self) -> u8 {
+        1 << (*self as u8)
+    }
 }
 
-fn visible_trees_in_direction_from(tree: &Tree, grid: &Grid, direction: Direction, stop: u32) -> u32 {
-    println!("Going {:?} from {:?}", direction, tree);
-    let next_tree: &Tree = match direction {
-        Direction::Left => {
-            if tree.x == 0 {
-                return 0;
-            } 
-
-            grid.trees.get(tree.y).unwrap().get(tree.x - 1).unwrap()
-        },
-        Direction::Right => {
-            if tree.x == grid.max_x - 1 {
-                return 0;
+// Walks a single line of heights from its start, which is the edge the trees
+// are looked at from. Keeps a stack of the trees which are not hidden behind
+// a later tree of at least the same height, so every tree is pushed and
+// popped once. For every tree returns whether it's visible from the edge and
+// how many trees it can see looking back towards the edge.
+pub fn skyline(heights: &[u32]) -> Vec<(bool, usize)> {
+    let mut stack: Vec<usize> = Vec::new();
+    let mut result = Vec::with_capacity(heights.len());
+
+    for (i, height) in heights.iter().enumerate() {
+        while let Some(&top) = stack.last() {
+            if heights[top] >= *height {
+                break;
             }
+            stack.pop();
+        }
 
-            grid.trees.get(tree.y).unwrap().get(tree.x + 1 ).unwrap()
-        },
-        Direction::Down => {
-            if tree.y == grid.max_y - 1 {
-                return 0;
-            }
-                
-            grid.trees.get(tree.y + 1).unwrap().get(tree.x).unwrap()
-        },
-        Direction::Top => {
-            if tree.y == 0 {
-                return 0;
-            } 
-
-            grid.trees.get(tree.y - 1).unwrap().get(tree.x).unwrap()
-        },
-    };
+        result.push(match stack.last() {
+            // nothing as tall in the way, so it sees all the way to the edge
+            None => (true, i),
+            Some(&blocker) => (false, i - blocker),
+        });
+        stack.push(i);
+    }
 
-    if next_tree.height >= stop {
-        return 1;
-    } 
-    
-    let neighbour = visible_trees_in_direction_from(
-        next_tree,
-        grid,
-        direction,
-        stop
-    );
-
-    return 1 + neighbour;
+    result
 }
 
-fn visibility_score(tree: &Tree, grid: &Grid) -> u32 {
-    let mut left = 0;
-    let mut right = 0;
-    let mut top = 0;
-    let mut bottom = 0;
-    if tree.x > 0 {
-        let next_tree = grid.trees.get(tree.y).unwrap().get(tree.x - 1).unwrap();
-        if next_tree.height >= tree.height {
-            left = 1;
-        } else {
-            left = 1 + visible_trees_in_direction_from(
-                next_tree,
-                grid,
-                Direction::Left,
-                tree.height
-            );
-        }
-    }
-    if tree.x < grid.max_x - 1 {
-        let next_tree = grid.trees.get(tree.y).unwrap().get(tree.x + 1).unwrap();
-        if next_tree.height >= tree.height {
-            right = 1;
-        } else {
-            right = 1 + visible_trees_in_direction_from(
-                next_tree,
-                grid,
+// Visibility of every tree from each of the four edges, and how far every
+// tree can see towards each of them. Indexed by [y][x], rows can be any
+// length as long as they're all the same.
+pub struct LineOfSight {
+    width: usize,
+    height: usize,
+    // bit per `Direction` the tree can be seen from
+    visible_from: Vec<Vec<u8>>,
+    // indexed by `Direction as usize`
+    viewing_distances: Vec<Vec<[usize; 4]>>,
+}
+
+impl LineOfSight {
+    pub fn new(heights: &[Vec<u32>]) -> LineOfSight {
+        let height = heights.len();
+        let width = heights.first().map(|row| row.len()).unwrap_or(0);
+        assert!(
+            heights.iter().all(|row| row.len() == width),
+            "Rows have different lengths"
+        );
+
+        let mut sight = LineOfSight {
+            width,
+            height,
+            visible_from: vec![vec![0; width]; height],
+            viewing_distances: vec![vec![[0; 4]; width]; height],
+        };
+
+        for y in 0..height {
+            let coordinates: Vec<(usize, usize)> = (0..width).map(|x| (x, y)).collect();
+            sight.sweep(heights, &coordinates, Direction::Left);
+            sight.sweep(
+                heights,
+                &coordinates.into_iter().rev().collect::<Vec<_>>(),
                 Direction::Right,
-                tree.height
             );
         }
-    }
-    if tree.y > 0 {
-        let next_tree = grid.trees.get(tree.y + 1).unwrap().get(tree.x).unwrap();
-        if next_tree.height >= tree.height {
-            bottom = 1;
-        } else {
-            bottom = 1 + visible_trees_in_direction_from(
-                next_tree,
-                grid,
+        for x in 0..width {
+            let coordinates: Vec<(usize, usize)> = (0..height).map(|y| (x, y)).collect();
+            sight.sweep(heights, &coordinates, Direction::Top);
+            sight.sweep(
+                heights,
+                &coordinates.into_iter().rev().collect::<Vec<_>>(),
                 Direction::Down,
-                tree.height
             );
         }
+
+        sight
     }
-    if tree.y < grid.max_y - 1 {
-        let next_tree = grid.trees.get(tree.y - 1).unwrap().get(tree.x).unwrap();
-        if next_tree.height >= tree.height {
-            top = 1;
-        } else {
-            top = 1 + visible_trees_in_direction_from(
-                next_tree,
-                grid,
-                Direction::Top,
-                tree.height
-            );
+
+    // `line` starts at the edge on the `direction` side.
+    fn sweep(&mut self, heights: &[Vec<u32>], line: &[(usize, usize)], direction: Direction) {
+        let line_heights: Vec<u32> = line.iter().map(|&(x, y)| heights[y][x]).collect();
+
+        for (&(x, y), (visible, distance)) in line.iter().zip(skyline(&line_heights)) {
+            if visible {
+                self.visible_from[y][x] |= direction.bit();
+            }
+            self.viewing_distances[y][x][direction as usize] = distance;
         }
     }
 
-    println!(
-        "SELF: {:?} |||||||||||| Left: {}, Right: {}, Top: {}, Bottom: {}",
-        tree,
-        left, right, top, bottom
-    );
+    pub fn is_visible_from(&self, x: usize, y: usize, direction: Direction) -> bool {
+        self.visible_from[y][x] & direction.bit() != 0
+    }
 
+    #[cfg(test)]
+    pub fn is_visible(&self, x: usize, y: usize) -> bool {
+        self.visible_from[y][x] != 0
+    }
 
-    return left * right * top * bottom;
-}
+    pub fn viewing_distance(&self, x: usize, y: usize, direction: Direction) -> usize {
+        self.viewing_distances[y][x][direction as usize]
+    }
 
+    pub fn scenic_score(&self, x: usize, y: usize) -> usize {
+        self.viewing_distances[y][x].iter().product()
+    }
 
-fn visible_outside(tree: &Tree, grid: &Grid) -> bool {
-    if tree.x == 0 || tree.x == grid.max_x - 1 || tree.y == 0 || tree.y == grid.max_y - 1 {
-        return true;
+    pub fn visibility_mask(&self) -> Vec<Vec<bool>> {
+        self.visible_from
+            .iter()
+            .map(|row| row.iter().map(|bits| *bits != 0).collect())
+            .collect()
     }
 
-    let mut left = 0;
-    let mut right = 0;
-    let mut top = 0;
-    let mut bottom = 0;
-    if tree.x > 0 {
-        left = determine_max_height(
-                &grid.trees.get(tree.y).unwrap().get(tree.x - 1).unwrap(),
-                grid,
-                Direction::Left,
-            );
+    #[cfg(test)]
+    pub fn viewing_distance_matrix(&self, direction: Direction) -> Vec<Vec<usize>> {
+        self.viewing_distances
+            .iter()
+            .map(|row| row.iter().map(|d| d[direction as usize]).collect())
+            .collect()
     }
-    if tree.x < grid.max_x - 1 {
-        right = determine_max_height(
-                &grid.trees.get(tree.y).unwrap().get(tree.x + 1).unwrap(),
-                grid,
-                Direction::Right,
-            );
+
+    pub fn scenic_scores(&self) -> Vec<Vec<usize>> {
+        (0..self.height)
+            .map(|y| (0..self.width).map(|x| self.scenic_score(x, y)).collect())
+            .collect()
     }
-    if tree.y > 0 {
-        top = determine_max_height(
-                &grid.trees.get(tree.y - 1).unwrap().get(tree.x).unwrap(),
-                grid,
-                Direction::Top,
-            );
+}
+
+impl Grid {
+    fn heights(&self) -> Vec<Vec<u32>> {
+        self.trees
+            .iter()
+            .map(|row| row.iter().map(|tree| tree.height).collect())
+            .collect()
     }
-    if tree.y < grid.max_y - 1 {
-        bottom = determine_max_height(
-                &grid.trees.get(tree.y + 1).unwrap().get(tree.x).unwrap(),
-                grid,
-                Direction::Down,
-            );
+
+    pub fn line_of_sight(&self) -> LineOfSight {
+        LineOfSight::new(&self.heights())
     }
+}
 
-    // println!(
-    //     "SELF: {:?} |||||||||||| Left: {}, Right: {}, Top: {}, Bottom: {}",
-    //     tree,
-    //     left, right, top, bottom
-    // );
+fn part1(grid: &Grid) -> usize {
+    grid.line_of_sight()
+        .visibility_mask()
+        .iter()
+        .flatten()
+        .filter(|visible| **visible)
+        .count()
+}
 
-    return tree.height > left || tree.height > right || tree.height > top || tree.height > bottom;
+fn part2(grid: &Grid) -> usize {
+    grid.line_of_sight()
+        .scenic_scores()
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(0)
 }
 
-fn part1(grid: &Grid) {
-    let mut visible = 0;
-    let mut edge_visible = 0;
-    for row in &grid.trees {
-        for tree in row {
-            if tree.x == 0 || tree.x == grid.max_x - 1 || tree.y == 0 || tree.y == grid.max_y - 1 {
-                edge_visible += 1;
-            } else if visible_outside(tree, grid) {
-                println!("Visible tree: {:?}", tree);
-                visible += 1;
-            }
-        }
-    }
+fn load(filename: &str) -> Grid {
+    let file = match File::open(filename) {
+        Err(why) => panic!("Couldn't open file {}", why),
+        Ok(file) => file,
+    };
 
-    println!("Visible: {}, edge_visible: {}", visible, edge_visible);
-    println!("Result: {}", visible + edge_visible);
+    parse(io::BufReader::new(file).lines())
 }
 
-fn part2(grid: &Grid) {
-    let mut max_score = 0;
-    for row in &grid.trees {
-        for tree in row {
-            if tree.x == 0 || tree.x == grid.max_x - 1 || tree.y == 0 || tree.y == grid.max_y - 1 {
-                continue;
-            } else {
-                let score = visibility_score(tree, grid);
-                max_score = max(max_score, score);
-                
-                println!("score: {}, tree: {:?}", score, tree);
-            }
-        }
-    }
+pub fn solve() {
+    let grid = load("inputs/8_input");
 
-    println!("Score: {}", max_score);
+    println!("{}", part1(&grid));
+    println!("{}", part2(&grid));
 }
 
-
-pub fn solve() {
-    let file = match File::open("inputs/8_input") {
-        Err(why) => panic!("Couldn't open file {}", why),
-        Ok(file) => file,
+// How many trees can be seen from every side and how far the best tree
+// house can see in every direction.
+pub fn views() {
+    let sight = load("inputs/8_input").line_of_sight();
+    let side = |direction: Direction| match direction {
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Top => "top",
+        Direction::Down => "bottom",
     };
 
-    let reader = io::BufReader::new(file).lines();
-    let grid = parse(reader);
-    grid.print();
+    for direction in Direction::ALL {
+        let visible = (0..sight.height)
+            .flat_map(|y| (0..sight.width).map(move |x| (x, y)))
+            .filter(|(x, y)| sight.is_visible_from(*x, *y, direction))
+            .count();
+        println!("{} trees visible from the {}", visible, side(direction));
+    }
 
-    // part1(&grid);
-    part2(&grid);
+    let best = (0..sight.height)
+        .flat_map(|y| (0..sight.width).map(move |x| (x, y)))
+        .max_by_key(|(x, y)| sight.scenic_score(*x, *y));
+    if let Some((x, y)) = best {
+        println!("Best tree house at {},{}", x, y);
+        for direction in Direction::ALL {
+            println!("  sees {} trees towards the {}", sight.viewing_distance(x, y, direction), side(direction));
+        }
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn visible_outside(x: usize, y: usize, grid: &Grid) -> bool {
+        grid.line_of_sight().is_visible(x, y)
+    }
+
+    fn visibility_score(x: usize, y: usize, grid: &Grid) -> usize {
+        grid.line_of_sight().scenic_score(x, y)
+    }
+
     fn grid() -> Grid {
         let file = match File::open("inputs/8_input_sanity") {
             Err(why) => panic!("Couldn't open file {}", why),
@@ -352,8 +274,8 @@ mod tests {
         };
 
         let reader = io::BufReader::new(file).lines();
-        let grid = parse(reader);
-        return grid;
+
+        parse(reader)
     }
 
     fn grid_big() -> Grid {
@@ -363,97 +285,57 @@ mod tests {
         };
 
         let reader = io::BufReader::new(file).lines();
-        let grid = parse(reader);
-        return grid;
+
+        parse(reader)
     }
 
     #[test]
     fn visible_edge() {
         let grid = grid();
+        let (width, height) = (grid.trees[0].len(), grid.trees.len());
 
-        assert_eq!(
-            true,
-            visible_outside(grid.trees.get(0).unwrap().get(0).unwrap(), &grid)
-        );
-        assert_eq!(
-            true,
-            visible_outside(
-                grid.trees.get(grid.max_y - 1).unwrap().get(0).unwrap(),
-                &grid
-            )
-        );
-        assert_eq!(
-            true,
-            visible_outside(
-                grid.trees
-                    .get(grid.max_y - 1)
-                    .unwrap()
-                    .get(grid.max_x - 1)
-                    .unwrap(),
-                &grid
-            )
-        );
-        assert_eq!(
-            true,
-            visible_outside(
-                grid.trees.get(0).unwrap().get(grid.max_x - 1).unwrap(),
-                &grid
-            )
-        );
+        assert!(visible_outside(0, 0, &grid));
+        assert!(visible_outside(0, height - 1, &grid));
+        assert!(visible_outside(width - 1, height - 1, &grid));
+        assert!(visible_outside(width - 1, 0, &grid));
     }
 
     #[test]
     fn not_visible_near_the_edge() {
         let grid = grid();
 
-        assert_eq!(
-            false,
-            visible_outside(grid.trees.get(1).unwrap().get(3).unwrap(), &grid)
-        );
-        assert_eq!(
-            false,
-            visible_outside(grid.trees.get(2).unwrap().get(2).unwrap(), &grid)
-        );
+        assert!(!visible_outside(3, 1, &grid));
+        assert!(!visible_outside(2, 2, &grid));
     }
 
     #[test]
     fn not_visible_in_the_middle() {
         let grid = grid();
 
-        assert_eq!(
-            false,
-            visible_outside(grid.trees.get(2).unwrap().get(2).unwrap(), &grid)
-        );
+        assert!(!visible_outside(2, 2, &grid));
     }
 
     #[test]
     fn visible_in_the_top_left() {
         let grid = grid();
 
-        assert_eq!(
-            true,
-            visible_outside(grid.trees.get(1).unwrap().get(1).unwrap(), &grid)
-        );
+        assert!(visible_outside(1, 1, &grid));
     }
 
     #[test]
     fn visible_in_the_top_middle() {
         let grid = grid();
 
-        assert_eq!(
-            true,
-            visible_outside(grid.trees.get(1).unwrap().get(2).unwrap(), &grid)
-        );
+        assert!(visible_outside(2, 1, &grid));
     }
 
-
     #[test]
     fn visibility_score_top_mid() {
         let grid = grid();
 
         assert_eq!(
             4,
-            visibility_score(grid.trees.get(1).unwrap().get(2).unwrap(), &grid)
+            visibility_score(2, 1, &grid)
         );
     }
 
@@ -463,7 +345,7 @@ mod tests {
 
         assert_eq!(
             8,
-            visibility_score(grid.trees.get(3).unwrap().get(2).unwrap(), &grid)
+            visibility_score(2, 3, &grid)
         );
     }
 
@@ -473,7 +355,7 @@ mod tests {
 
         assert_eq!(
             1,
-            visibility_score(grid.trees.get(3).unwrap().get(1).unwrap(), &grid)
+            visibility_score(1, 3, &grid)
         );
     }
 
@@ -483,12 +365,10 @@ mod tests {
 
         assert_eq!(
             1,
-            visibility_score(grid.trees.get(2).unwrap().get(2).unwrap(), &grid)
+            visibility_score(2, 2, &grid)
         );
     }
 
-
-
     #[test]
     fn visibility_score_gird_big() {
         let grid = grid_big();
@@ -496,7 +376,43 @@ mod tests {
         println!("{:?}", grid.trees.get(77).unwrap());
         assert_eq!(
             1,
-            visibility_score(grid.trees.get(77).unwrap().get(43).unwrap(), &grid)
+            visibility_score(43, 77, &grid)
+        );
+    }
+
+    #[test]
+    fn sanity_answers() {
+        let grid = grid();
+
+        assert_eq!(21, part1(&grid));
+        assert_eq!(8, part2(&grid));
+    }
+
+    #[test]
+    fn skyline_scan() {
+        assert_eq!(
+            vec![(true, 0), (false, 1), (true, 2), (false, 1), (false, 1)],
+            skyline(&[2, 1, 3, 3, 1])
+        );
+    }
+
+    #[test]
+    fn non_square_grid() {
+        let heights = vec![vec![3, 0, 3, 7, 3, 1], vec![2, 5, 5, 1, 2, 0]];
+        let sight = LineOfSight::new(&heights);
+
+        // every tree is on an edge
+        assert!(sight.visibility_mask().iter().flatten().all(|v| *v));
+        assert!(!sight.is_visible_from(1, 0, Direction::Left));
+        assert!(sight.is_visible_from(3, 0, Direction::Right));
+        assert_eq!(
+            vec![vec![0, 1, 2, 3, 1, 1], vec![0, 1, 1, 1, 2, 1]],
+            sight.viewing_distance_matrix(Direction::Left)
+        );
+        assert_eq!(
+            vec![vec![0, 0, 0, 0, 0, 0], vec![1, 1, 1, 1, 1, 1]],
+            sight.viewing_distance_matrix(Direction::Top)
         );
+        assert_eq!(vec![vec![0; 6]; 2], sight.scenic_scores());
     }
 }
@@ -1,18 +1,11 @@
 use vector2d::Vector2D;
-use std::borrow::BorrowMut;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufRead};
 use regex::Regex;
-use strum_macros::EnumIter;
-use strum::IntoEnumIterator; // 0.17.1
 
-
-const DEBUG: bool = false;
-
-#[derive(Debug, EnumIter)]
-enum Direction {
-    Overlap,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
     Up,
     Down,
     Right,
@@ -24,28 +17,8 @@ enum Direction {
 }
 
 impl Direction {
-    fn opposite(&self) -> Direction {
-        match self {
-            Direction::Overlap => Direction::Overlap,
-            // if tail is Up, move tail down
-            Direction::Up => Direction::Down,
-            Direction::Down => Direction::Up,
-            Direction::Right => Direction::Left,
-            Direction::Left => Direction::Right,
-            // .H
-            // ..
-            // T.
-            // if tails is Right Up, move
-            Direction::RightUp => Direction::LeftDown,
-            Direction::LeftUp => Direction::RightDown,
-            Direction::RightDown => Direction::LeftUp,
-            Direction::LeftDown => Direction::RightUp,
-        }
-    }
-
     fn delta(&self) -> Vector2D<i32> {
         match self {
-            Direction::Overlap => Vector2D::new(0, 0),
             Direction::Up => Vector2D::new(0, 1),
             Direction::Down => Vector2D::new(0, -1),
             Direction::Right => Vector2D::new(1, 0),
@@ -58,455 +31,531 @@ impl Direction {
     }
 }
 
-struct Move {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
     direction: Direction,
     times: u32,
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Knot {
-    x: i32,
-    y: i32,
+impl Move {
+    // Diagonal moves can be written either way round, `UR` is the same as `RU`.
+    fn from(letters: &str, times: u32) -> Move {
+        let mut sorted: Vec<char> = letters.chars().collect();
+        sorted.sort_unstable();
+
+        Move {
+            direction: match sorted.iter().collect::<String>().as_str() {
+                "D" => Direction::Down,
+                "U" => Direction::Up,
+                "R" => Direction::Right,
+                "L" => Direction::Left,
+                "RU" => Direction::RightUp,
+                "LU" => Direction::LeftUp,
+                "DR" => Direction::RightDown,
+                "DL" => Direction::LeftDown,
+                _ => panic!("Unknown move {}!", letters)
+            },
+            times
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Knot {
+    pub x: i32,
+    pub y: i32,
 }
 
 impl Knot {
-    // 3 - 4 + 1 - 0
     fn touching(&self, other: &Knot) -> bool {
-        for direction in Direction::iter() {
-            let delta = direction.delta();
-            if self.x + delta.x == other.x && self.y + delta.y == other.y {
-                return true;
-            }
-        }
-        return false;
-    }
-
-    // from the Head perspective
-    fn relative(&self, head: &Knot) -> Direction {
-        let tail = self;
-        if tail.x == head.x && tail.y == head.y {
-            Direction::Overlap
-        } else if tail.x == head.x && tail.y > head.y {
-            Direction::Up
-        } else if tail.x == head.x && tail.y < head.y {
-            Direction::Down
-        } else if tail.x < head.x && tail.y == head.y {
-            Direction::Left
-        } else if tail.x > head.x && tail.y == head.y {
-            Direction::Right
-        } else if tail.x < head.x && tail.y < head.y {
-            Direction::LeftDown
-        } else if tail.x > head.x && tail.y < head.y {
-            Direction::RightDown
-        } else if tail.x < head.x && tail.y > head.y {
-            Direction::LeftUp
-        } else if tail.x > head.x && tail.y > head.y {
-            Direction::RightUp
-        } else {
-            panic!("Unexpected position");
-        }
+        (self.x - other.x).abs() <= 1 && (self.y - other.y).abs() <= 1
     }
-}
 
-fn display(tail: &Knot, head: &Knot, counter: &HashSet<String>) {
-    for y in (0..200).rev() {
-        for x in 0..200 {
-            if tail.x == x && tail.y == y {
-                print!("T");
-            } else if head.x == x && head.y == y {
-                print!("H");
-            } else if counter.contains(&format!("{}|{}", x, y).to_string()) {
-                print!("#");
-            } else {
-                print!(".");
-            }
+    // Moves one step towards `leader`, diagonally when not in the same row
+    // or column, unless the two are already touching.
+    fn follow(&mut self, leader: &Knot) -> bool {
+        if self.touching(leader) {
+            return false;
         }
 
-        println!();
+        self.x += (leader.x - self.x).signum();
+        self.y += (leader.y - self.y).signum();
+        true
     }
 
-    println!();
+    fn point(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
 }
 
-impl Move {
-    fn execute(&self, tail: &mut Knot, head: &mut Knot, counter: &mut HashSet<String>) {
-        if DEBUG {
-            display(tail, head, &counter);
-        }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
 
-        counter.insert(format!("{}|{}", tail.x, tail.y));
+impl Bounds {
+    fn around(x: i32, y: i32) -> Bounds {
+        Bounds {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+        }
+    }
 
-        for _ in 0..self.times {
-            let delta = self.direction.delta();
-            head.x += delta.x;
-            head.y += delta.y;
+    fn extend(&mut self, x: i32, y: i32) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
 
+    fn union(&self, other: &Bounds) -> Bounds {
+        let mut bounds = *self;
+        bounds.extend(other.min_x, other.min_y);
+        bounds.extend(other.max_x, other.max_y);
+        bounds
+    }
 
-            if DEBUG {
-                display(tail, head, &counter);
-            }
+    pub fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
 
-            if tail.touching(head) {
-                if DEBUG {
-                    display(tail, head, &counter);
-                }
-                continue;
-            }
-            if DEBUG {
-                println!("Not touching");
-            }
+    pub fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+}
 
-            let tail_delta = tail.relative(head).opposite().delta();
-            tail.x += tail_delta.x;
-            tail.y += tail_delta.y;
+// Everything a single knot did during a run.
+#[derive(Debug, Clone)]
+pub struct KnotStats {
+    // how many times the knot entered each cell, the start counts as a visit
+    pub visits: HashMap<(i32, i32), usize>,
+    // head step after which the knot got to the cell for the first time
+    pub first_visits: HashMap<(i32, i32), usize>,
+    // number of single cell moves, a diagonal one counts as one move
+    pub distance: usize,
+    pub bounds: Bounds,
+}
 
-            counter.insert(format!("{}|{}", tail.x, tail.y));
-            if DEBUG {
-                display(tail, head, &counter);
-            }
+impl KnotStats {
+    fn new() -> KnotStats {
+        KnotStats {
+            visits: HashMap::from([((0, 0), 1)]),
+            first_visits: HashMap::from([((0, 0), 0)]),
+            distance: 0,
+            bounds: Bounds::around(0, 0),
         }
     }
 
-    fn execute_multiple(&self, tails: &mut Vec<Knot>, head: &mut Knot, counter: &mut HashSet<String>) {
-        counter.insert(format!("{}|{}", tails.last().unwrap().x, tails.last().unwrap().y));
+    fn record(&mut self, point: (i32, i32), step: usize) {
+        *self.visits.entry(point).or_insert(0) += 1;
+        self.first_visits.entry(point).or_insert(step);
+        self.distance += 1;
+        self.bounds.extend(point.0, point.1);
+    }
 
-        let number_of_tails = tails.len();
+    pub fn visited(&self) -> HashSet<(i32, i32)> {
+        self.visits.keys().copied().collect()
+    }
 
-        for _ in 0..self.times {
-            let delta = self.direction.delta();
-            head.x += delta.x;
-            head.y += delta.y;
+    pub fn heatmap(&self) -> Heatmap {
+        let mut counts = vec![vec![0; self.bounds.width()]; self.bounds.height()];
+        for ((x, y), count) in &self.visits {
+            counts[(self.bounds.max_y - y) as usize][(x - self.bounds.min_x) as usize] = *count;
+        }
 
+        Heatmap {
+            bounds: self.bounds,
+            counts,
+        }
+    }
+}
 
-            for index in 0..number_of_tails {
-                let current_head: Knot = if index == 0 {
-                    head.clone()
-                } else {
-                    tails.get(index - 1).unwrap().clone()
-                };
+// Visit counts over the bounding box of a knot, the top row is the highest y.
+pub struct Heatmap {
+    pub bounds: Bounds,
+    pub counts: Vec<Vec<usize>>,
+}
 
-                let mut current_tail = tails.get_mut(index).unwrap();
+impl Heatmap {
+    // First row holds the x coordinates and every other row starts with its y.
+    pub fn to_csv(&self) -> String {
+        let header: Vec<String> = (self.bounds.min_x..=self.bounds.max_x)
+            .map(|x| x.to_string())
+            .collect();
+        let mut lines = vec![format!("y\\x,{}", header.join(","))];
+
+        for (row, y) in self.counts.iter().zip((self.bounds.min_y..=self.bounds.max_y).rev()) {
+            let cells: Vec<String> = row.iter().map(|count| count.to_string()).collect();
+            lines.push(format!("{},{}", y, cells.join(",")));
+        }
 
-                if current_tail.touching(&current_head) {
-                    continue;
-                }
+        lines.join("\n") + "\n"
+    }
 
-                let tail_delta = current_tail.relative(&current_head).opposite().delta();
-                current_tail.x += tail_delta.x;
-                current_tail.y += tail_delta.y;
+    // Plain (P2) greymap, the most visited cell is white.
+    pub fn to_pgm(&self) -> String {
+        let max = self.counts.iter().flatten().copied().max().unwrap_or(0).clamp(1, 65535);
+        let mut lines = vec![
+            "P2".to_string(),
+            format!("{} {}", self.bounds.width(), self.bounds.height()),
+            max.to_string(),
+        ];
 
-                if index == number_of_tails - 1 {
-                    counter.insert(format!("{}|{}", current_tail.x, current_tail.y));
-                }
-            }
+        for row in &self.counts {
+            let cells: Vec<String> = row.iter().map(|count| (*count).min(max).to_string()).collect();
+            lines.push(cells.join(" "));
         }
-    }
 
-    fn from(letter: &str, times: u32) -> Move {
-        Move {
-            direction: match letter {
-                "D" => Direction::Down,
-                "U" => Direction::Up,
-                "R" => Direction::Right,
-                "L" => Direction::Left,
-                _ => panic!("Unknown move!")
-            },
-            times: times
-        }
+        lines.join("\n") + "\n"
     }
 }
 
-fn parse(reader: io::Lines<io::BufReader<File>>) -> Vec<Move> {
-    let move_regex = Regex::new(r"^(?P<letter>[A-Z]) (?P<times>\d+).*").unwrap();
+// A rope with the head at index 0, every other knot follows the one before it.
+// Keeps track of what every single knot did along the way.
+pub struct Rope {
+    knots: Vec<Knot>,
+    stats: Vec<KnotStats>,
+    steps: usize,
+}
 
-    let mut moves = Vec::new();
-    for line in reader {
-        if let Ok(l) = line {
-            let captures = move_regex.captures(&l).unwrap();
-            let letter = captures.name("letter").unwrap().as_str();
-            let times: u32 = captures.name("times").unwrap().as_str().parse().unwrap();
+impl Rope {
+    pub fn new(knot_count: usize) -> Rope {
+        assert!(knot_count > 0, "A rope needs at least a head");
 
-            moves.push(Move::from(letter, times));
+        Rope {
+            knots: vec![Knot::default(); knot_count],
+            stats: vec![KnotStats::new(); knot_count],
+            steps: 0,
         }
     }
 
-    moves
-}
+    pub fn head(&self) -> Knot {
+        self.knots[0]
+    }
 
-fn part1(reader: io::Lines<io::BufReader<File>>) {
-    let mut head = Knot { x: 0, y: 0 };
-    let mut tail = Knot { x: 0, y: 0 };
+    pub fn tail(&self) -> Knot {
+        *self.knots.last().unwrap()
+    }
 
-    let head_moves = parse(reader);
+    #[cfg(test)]
+    pub fn knots(&self) -> &[Knot] {
+        &self.knots
+    }
 
-    let mut counter = HashSet::new();
+    // Number of single head steps taken so far.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
 
-    for head_move in head_moves {
-        head_move.execute(&mut tail, &mut head, &mut counter);
+    pub fn stats(&self, knot: usize) -> &KnotStats {
+        &self.stats[knot]
     }
 
+    pub fn visited(&self, knot: usize) -> HashSet<(i32, i32)> {
+        self.stats[knot].visited()
+    }
 
-    println!("Head: {:?}, Tail: {:?}, count: {:?}", head, tail, counter.len());
-}
+    pub fn tail_visited(&self) -> HashSet<(i32, i32)> {
+        self.stats.last().unwrap().visited()
+    }
+
+    // Moves the head by a single cell and lets the rest of the rope catch up.
+    pub fn step(&mut self, direction: Direction) {
+        self.steps += 1;
+
+        let delta = direction.delta();
+        self.knots[0].x += delta.x;
+        self.knots[0].y += delta.y;
+        self.stats[0].record(self.knots[0].point(), self.steps);
+
+        for index in 1..self.knots.len() {
+            let leader = self.knots[index - 1];
+            if !self.knots[index].follow(&leader) {
+                // nothing behind this knot is going to move either
+                break;
+            }
+            self.stats[index].record(self.knots[index].point(), self.steps);
+        }
+    }
+
+    pub fn execute(&mut self, head_move: &Move) {
+        for _ in 0..head_move.times {
+            self.step(head_move.direction);
+        }
+    }
+
+    // Positions of all the knots after every single step of the moves.
+    #[cfg(test)]
+    pub fn snapshots<'a>(&'a mut self, moves: &'a [Move]) -> impl Iterator<Item = Vec<Knot>> + 'a {
+        moves
+            .iter()
+            .flat_map(|m| std::iter::repeat_n(m.direction, m.times as usize))
+            .map(move |direction| {
+                self.step(direction);
+                self.knots.clone()
+            })
+    }
 
-fn display_multiple(head: &Knot, tails: &Vec<Knot>) {
-    for y in (-10..15).rev() {
-        for x in -11..15 {
-            let mut somethings_there = false;
-             if head.x == x && head.y == y {
-                print!("H");
-            } else {
-                for (index, tail) in tails.iter().enumerate() {
-                    if tail.x == x && tail.y == y {
-                        print!("{}", index + 1);
-                        somethings_there = true;
-                        break;
-                    }
+    // Draws the rope the same way as the puzzle, over the area every knot
+    // has been to so far, with the cells visited by the tail marked with `#`.
+    pub fn render(&self) -> String {
+        let bounds = self
+            .stats
+            .iter()
+            .fold(Bounds::around(0, 0), |bounds, stats| bounds.union(&stats.bounds));
+        let tail_visited = self.tail_visited();
+
+        let mut lines = Vec::new();
+        for y in (bounds.min_y..=bounds.max_y).rev() {
+            let mut line = String::new();
+            for x in bounds.min_x..=bounds.max_x {
+                match self.knots.iter().position(|knot| knot.point() == (x, y)) {
+                    Some(0) => line.push('H'),
+                    Some(index) if index == self.knots.len() - 1 => line.push('T'),
+                    Some(index) => line.push_str(&index.to_string()),
+                    None if (x, y) == (0, 0) => line.push('s'),
+                    None if tail_visited.contains(&(x, y)) => line.push('#'),
+                    None => line.push('.'),
                 }
-            } 
-                
-            if !somethings_there && x == 0 && y == 0 {
-                print!("s");
-            } else if !somethings_there {
-                print!(".");
             }
+            lines.push(line);
         }
-        println!();
+
+        lines.join("\n")
     }
 }
 
-fn part2(reader: io::Lines<io::BufReader<File>>) {
-    let (mut head, mut tails) = knots_multiple(9);
-
-    let head_moves = parse(reader);
+fn parse(reader: io::Lines<io::BufReader<File>>) -> Vec<Move> {
+    let move_regex = Regex::new(r"^(?P<letters>[A-Z]{1,2}) (?P<times>\d+).*").unwrap();
 
-    let mut counter = HashSet::new();
+    let mut moves = Vec::new();
+    for l in reader.map_while(Result::ok) {
+        let captures = move_regex.captures(&l).unwrap();
+        let letters = captures.name("letters").unwrap().as_str();
+        let times: u32 = captures.name("times").unwrap().as_str().parse().unwrap();
 
-    for head_move in head_moves {
-        head_move.execute_multiple(&mut tails, &mut head, &mut counter);
+        moves.push(Move::from(letters, times));
     }
 
-    // display_multiple(&head, &tails);
+    moves
+}
 
+fn simulate(moves: &[Move], knot_count: usize) -> Rope {
+    let mut rope = Rope::new(knot_count);
+    for head_move in moves {
+        rope.execute(head_move);
+    }
 
-    println!("Head: {:?}, count: {:?}", head, counter.len());
+    rope
 }
 
-pub fn solve() {
-    let file = match File::open("inputs/9_input") {
+fn load(filename: &str) -> Vec<Move> {
+    let file = match File::open(filename) {
         Err(why) => panic!("Couldn't open file {}", why),
         Ok(file) => file,
     };
 
-    let reader = io::BufReader::new(file).lines();
-    // part1(reader);
-    part2(reader);
-
+    parse(io::BufReader::new(file).lines())
 }
 
-fn knots() -> (Knot, Knot) {
-    let head = Knot { x: 0, y: 0 };
-    let tail = Knot { x: 0, y: 0 };
+pub fn solve() {
+    let moves = load("inputs/9_input");
 
-    (head, tail)
+    println!("{}", simulate(&moves, 2).tail_visited().len());
+    println!("{}", simulate(&moves, 10).tail_visited().len());
 }
 
-fn knots_multiple(tails_size: usize) -> (Knot, Vec<Knot>) {
-    let head = Knot { x: 0, y: 0 };
-    let mut tails: Vec<Knot>  = Vec::new();
-
-    for _ in 0..tails_size {
-        tails.push(Knot {
-            x: 0,
-            y: 0
-        })
+// What every knot of the long rope did, `csv` or `pgm` give the tail's
+// heatmap instead.
+pub fn report(format: Option<&str>) {
+    let rope = simulate(&load("inputs/9_input"), 10);
+    let tail = rope.stats(9);
+
+    match format {
+        Some("csv") => print!("{}", tail.heatmap().to_csv()),
+        Some("pgm") => print!("{}", tail.heatmap().to_pgm()),
+        _ => {
+            let (head, tail) = (rope.head(), rope.tail());
+            println!("{} steps, head at {},{}, tail at {},{}", rope.steps(), head.x, head.y, tail.x, tail.y);
+            for knot in 0..10 {
+                let stats = rope.stats(knot);
+                println!(
+                    "knot {}: moved {} times, visited {} cells in a {}x{} area",
+                    knot,
+                    stats.distance,
+                    rope.visited(knot).len(),
+                    stats.bounds.width(),
+                    stats.bounds.height()
+                );
+            }
+            println!("{}", rope.render());
+        }
     }
-
-    (head, tails)
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn base_moves() -> Vec<Move> {
+        vec![
+            Move::from("R", 4),
+            Move::from("U", 4),
+            Move::from("L", 3),
+            Move::from("D", 1),
+            Move::from("R", 4),
+            Move::from("D", 1),
+            Move::from("L", 5),
+            Move::from("R", 2),
+        ]
+    }
+
     #[test]
     fn follows_right() {
-        let (mut head, mut tail) = knots();
+        let mut rope = Rope::new(2);
 
-        let head_move = Move {
+        rope.execute(&Move {
             direction: Direction::Right,
             times: 4,
-        };
-
-        head_move.execute(&mut tail, &mut head, &mut HashSet::new());
+        });
 
-        assert_eq!(4, head.x);
-        assert_eq!(0, head.y);
-        assert_eq!(3, tail.x);
-        assert_eq!(0, tail.y);
+        assert_eq!(4, rope.head().x);
+        assert_eq!(0, rope.head().y);
+        assert_eq!(3, rope.tail().x);
+        assert_eq!(0, rope.tail().y);
     }
 
     #[test]
     fn base_case() {
-        let (mut head, mut tail) = knots();
-        let mut counter = HashSet::new();
-        let head_moves = vec![
-            Move {
-                direction: Direction::Right,
-                times: 4,
-            },
-            Move {
-                direction: Direction::Up,
-                times: 4,
-            },
-            Move {
-                direction: Direction::Left,
-                times: 3,
-            },
-            Move {
-                direction: Direction::Down,
-                times: 1,
-            },
-            Move {
-                direction: Direction::Right,
-                times: 4,
-            },
-            Move {
-                direction: Direction::Down,
-                times: 1,
-            },
-            Move {
-                direction: Direction::Left,
-                times: 5,
-            },
-            Move {
-                direction: Direction::Right,
-                times: 2,
-            },
-        ];
-    
-        for head_move in head_moves {
-            head_move.execute(&mut tail, &mut head, &mut counter);
-        }
-    
-        assert_eq!(2, head.x);
-        assert_eq!(2, head.y);
-        assert_eq!(1, tail.x);
-        assert_eq!(2, tail.y);
-        assert_eq!(13, counter.len())
+        let rope = simulate(&base_moves(), 2);
+
+        assert_eq!(2, rope.head().x);
+        assert_eq!(2, rope.head().y);
+        assert_eq!(1, rope.tail().x);
+        assert_eq!(2, rope.tail().y);
+        assert_eq!(13, rope.tail_visited().len())
     }
 
     #[test]
     fn multiple_heads() {
-        let (mut head, mut tails) = knots_multiple(3);
-        let mut counter = HashSet::new();
+        let mut rope = Rope::new(4);
 
-        let head_move = Move {
+        rope.execute(&Move {
             direction: Direction::Right,
             times: 4,
-        };
-
-        head_move.execute_multiple(&mut tails, &mut head, &mut counter);
+        });
 
-        // 
-        assert_eq!(4, head.x);
-        assert_eq!(0, head.y);
+        assert_eq!(4, rope.head().x);
+        assert_eq!(0, rope.head().y);
 
-        assert_eq!(3, tails.get(0).unwrap().x);
-        assert_eq!(0, tails.get(0).unwrap().y);
+        assert_eq!(Knot { x: 3, y: 0 }, rope.knots()[1]);
+        assert_eq!(Knot { x: 2, y: 0 }, rope.knots()[2]);
+        assert_eq!(Knot { x: 1, y: 0 }, rope.knots()[3]);
+    }
 
-        assert_eq!(2, tails.get(1).unwrap().x);
-        assert_eq!(0, tails.get(1).unwrap().y);
+    #[test]
+    fn multiple_heads_base_case() {
+        let rope = simulate(&base_moves(), 11);
 
-        assert_eq!(1, tails.get(2).unwrap().x);
-        assert_eq!(0, tails.get(2).unwrap().y);
+        assert_eq!(2, rope.head().x);
+        assert_eq!(2, rope.head().y);
+        assert_eq!(1, rope.tail_visited().len())
     }
 
     #[test]
-    fn multiple_heads_base_case() {
-        let (mut head, mut tails) = knots_multiple(10);
-        let mut counter = HashSet::new();
-        let head_moves = vec![
-            Move {
-                direction: Direction::Right,
-                times: 4,
-            },
-            Move {
-                direction: Direction::Up,
-                times: 4,
-            },
-            Move {
-                direction: Direction::Left,
-                times: 3,
-            },
-            Move {
-                direction: Direction::Down,
-                times: 1,
-            },
-            Move {
-                direction: Direction::Right,
-                times: 4,
-            },
-            Move {
-                direction: Direction::Down,
-                times: 1,
-            },
-            Move {
-                direction: Direction::Left,
-                times: 5,
-            },
-            Move {
-                direction: Direction::Right,
-                times: 2,
-            },
+    fn larger_example() {
+        let moves = vec![
+            Move::from("R", 5),
+            Move::from("U", 8),
+            Move::from("L", 8),
+            Move::from("D", 3),
+            Move::from("R", 17),
+            Move::from("D", 10),
+            Move::from("L", 25),
+            Move::from("U", 20),
         ];
-    
-        for head_move in head_moves {
-            head_move.execute_multiple(&mut tails, &mut head, &mut counter);
-        }
-    
-        assert_eq!(2, head.x);
-        assert_eq!(2, head.y);
-        assert_eq!(1, counter.len())
+        let rope = simulate(&moves, 10);
+
+        assert_eq!(36, rope.tail_visited().len());
+        assert_eq!(Knot { x: -11, y: 15 }, rope.head());
+        assert!(rope.visited(1).len() > rope.visited(9).len());
     }
 
-}
+    #[test]
+    fn diagonal_moves() {
+        assert_eq!(Move::from("UR", 1), Move::from("RU", 1));
+        assert_eq!(Direction::LeftDown, Move::from("DL", 3).direction);
+
+        let rope = simulate(&[Move::from("UR", 3), Move::from("DL", 1)], 3);
+        assert_eq!(Knot { x: 2, y: 2 }, rope.head());
+        // diagonal head moves pull every knot along the diagonal as well
+        assert_eq!(Knot { x: 2, y: 2 }, rope.knots()[1]);
+        assert_eq!(Knot { x: 1, y: 1 }, rope.knots()[2]);
+        assert_eq!(HashSet::from([(0, 0), (1, 1)]), rope.tail_visited());
+    }
 
-// Head (0, 0)
-// Tail (0, 0)
-// Up      +(0, 1)
-// Down    +(0, -1)
-// Right   +(1, 0)
-// Left    +(-1, 0)
-// RightUp +(1, 1)
-// LeftUp  +(-1, 1)
-// RightDown +(1, -1)
-// LeftDown +(0, -1)
-
-// touching?
-// tail.x == head.x
-// tail.y == head.y
-// tail.x + 1 == head.x
-// tail.x - 1 == head.x
-// tail.y + 1 == head.y
-// tail.y - 1 == head.y
-
-// Where is the Tail, from the Head perspective
-// H(0, 0) T(0, 0) -> Overlap tail.x == head.x && tail.y == head.y
-
-// H(0, -1) T(0, 0) -> Up tail.x == head.x && tail.y > head.y
-// H(0, 1) T(0, 0) -> Down tail.x == head.x && tail.y < head.y
-
-// H(1, 0) T(0, 0) -> Left tail.x < head.x && tail.y == head.y
-// H(-1, 0) T(0, 0) -> Right tail.x > head.x && tail.y == head.y
-
-// H(1, 1) T(0, 0) -> LeftDown tail.x < head.x && tail.y < head.y
-// H(-1, 1) T(0, 0) ->  RightDown tail.x > head.x && tail.y < head.y
-
-// H(1, -1) T(0, 0) -> LeftUp   tail.x < head.x && tail.y > head.y
-// H(-1, -1) T(0, 0) -> RightUp  tailx > head.x && tail.y > head.y
-
-// is touching, do not move
-// we move Head
-// determine where we are,
-// if touching -> do not move
-// else -> move in the counter direction.
+    #[test]
+    fn per_step_snapshots() {
+        let moves = [Move::from("R", 2), Move::from("U", 1)];
+        let mut rope = Rope::new(2);
+        let snapshots: Vec<Vec<Knot>> = rope.snapshots(&moves).collect();
+
+        assert_eq!(
+            vec![
+                vec![Knot { x: 1, y: 0 }, Knot { x: 0, y: 0 }],
+                vec![Knot { x: 2, y: 0 }, Knot { x: 1, y: 0 }],
+                vec![Knot { x: 2, y: 1 }, Knot { x: 1, y: 0 }],
+            ],
+            snapshots
+        );
+        assert_eq!(3, rope.steps());
+    }
+
+    #[test]
+    fn knot_statistics() {
+        let rope = simulate(&[Move::from("R", 3), Move::from("L", 4)], 2);
+        let head = rope.stats(0);
+        let tail = rope.stats(1);
+
+        assert_eq!(7, head.distance);
+        assert_eq!(Bounds { min_x: -1, min_y: 0, max_x: 3, max_y: 0 }, head.bounds);
+        // the head went through 1 and 2 twice, 0 counts the start as well
+        assert_eq!(Some(&2), head.visits.get(&(1, 0)));
+        assert_eq!(Some(&2), head.visits.get(&(0, 0)));
+        assert_eq!(Some(&1), head.visits.get(&(3, 0)));
+        assert_eq!(Some(&7), head.first_visits.get(&(-1, 0)));
+
+        // R3 drags the tail to 2, L4 pulls it back to 0
+        assert_eq!(4, tail.distance);
+        assert_eq!(Some(&2), tail.first_visits.get(&(1, 0)));
+        assert_eq!(Some(&3), tail.first_visits.get(&(2, 0)));
+        assert_eq!(Some(&2), tail.visits.get(&(1, 0)));
+        assert_eq!(HashSet::from([(0, 0), (1, 0), (2, 0)]), tail.visited());
+    }
+
+    #[test]
+    fn heatmap_export() {
+        let rope = simulate(&[Move::from("U", 1), Move::from("L", 2), Move::from("R", 2)], 1);
+        let heatmap = rope.stats(0).heatmap();
+
+        assert_eq!("y\\x,-2,-1,0\n1,1,2,2\n0,0,0,1\n", heatmap.to_csv());
+        assert_eq!("P2\n3 2\n2\n1 2 2\n0 0 1\n", heatmap.to_pgm());
+    }
+
+    #[test]
+    fn render_negative_coordinates() {
+        let rope = simulate(&[Move::from("L", 3), Move::from("D", 2)], 3);
+
+        assert_eq!(
+            "..#s
+1T..
+H...",
+            rope.render()
+        );
+    }
+}
@@ -1,14 +1,62 @@
 // mod day4;
-// mod day5;
-// mod day6;
-// mod day8;
-// mod day9;
-// mod day13;
-// mod day14;
-// mod day16;
+mod day5;
+mod day6;
+mod day7;
+mod day8;
+mod day9;
+mod day10;
+mod day11;
+mod day12;
+mod day13;
+mod day14;
+mod day15;
+mod day16;
 mod day17;
 
 
+// Runs the day given as the first argument, the latest one without any.
+// Some days have extra modes, e.g. `10 debug`, `13 explain` or `7 du /a`.
 fn main() {
-    day17::solve();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let day = match args.first() {
+        Some(day) => day.as_str(),
+        None => "17",
+    };
+
+    match (day, args.get(1).map(String::as_str)) {
+        ("5", _) => {
+            day5::part1();
+            day5::part2();
+        }
+        ("6", _) => {
+            day6::part1();
+            day6::part2();
+        }
+        ("7", Some("du")) => day7::report(args.get(2).map(String::as_str)),
+        ("7", _) => day7::solve(),
+        ("8", Some("views")) => day8::views(),
+        ("8", _) => day8::solve(),
+        ("9", Some("rope")) => day9::report(None),
+        ("9", Some(format @ ("csv" | "pgm"))) => day9::report(Some(format)),
+        ("9", _) => day9::solve(),
+        ("10", Some("debug")) => day10::debug(),
+        ("10", _) => day10::solve(),
+        ("11", Some("trace")) => day11::trace(args.get(2).map(String::as_str)),
+        ("11", _) => day11::solve(),
+        ("12", _) => day12::solve(),
+        ("13", Some("explain")) => day13::explain_pairs(),
+        ("13", Some("json")) => day13::inspect_json(),
+        ("13", _) => day13::solve(),
+        ("14", Some("scenario")) => match args.get(2) {
+            Some(filename) => day14::run_scenario(filename),
+            None => println!("Which scenario?"),
+        },
+        ("14", _) => day14::solve(),
+        ("15", Some("scan")) => day15::scan(),
+        ("15", _) => day15::solve(),
+        ("16", Some("show")) => day16::show(args.get(2).map(String::as_str)),
+        ("16", _) => day16::solve(),
+        ("17", _) => day17::solve(),
+        (day, _) => println!("There's no solution for day {}", day),
+    }
 }